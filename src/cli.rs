@@ -0,0 +1,133 @@
+use crate::exam::Severity;
+use crate::pack::{Manifest, Pack};
+use crate::{Config, FsStore, Result, SqliteStore, Store};
+
+const USAGE: &str = "usage: elearn_editor [--root <dir>] [--port <port>] [--config <file>] [--store fs|memory|sqlite] [--session-ttl <secs>] [<command>]
+
+commands:
+    import-sqlite    import banks and history under the data root into data/elearn.db
+    lint             check every bank in the store and report problems
+    upgrade-packs    install missing bundled banks and upgrade the ones left unedited
+    export-pack <file> <type> <bank>... [version=<v>] [author=<who>]
+                     pack banks of one type into <file>, named after its stem
+    import-pack <file>
+                     import the banks and media of a pack, keeping existing banks that differ";
+
+/// 以存储 `store` 执行 `elearn_editor` 的子命令；只有写入题库的命令会安装内置题库
+pub fn run(cfg: &Config, store: &dyn Store) -> Result<()> {
+    let args: Vec<&str> = cfg.command.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["import-sqlite"] => import_sqlite(cfg),
+        ["lint"] => lint(store),
+        ["upgrade-packs"] => upgrade_packs(cfg, store),
+        ["export-pack", file, typ, rest @ ..] if !rest.is_empty() => {
+            export_pack(cfg, store, file, typ, rest)
+        }
+        ["import-pack", file] => import_pack(cfg, store, file),
+        _ => {
+            eprintln!("{}", USAGE);
+            Err(format!("unknown command {:?}", args).into())
+        }
+    }
+}
+
+fn import_sqlite(cfg: &Config) -> Result<()> {
+    let db = SqliteStore::open(cfg)?;
+    let report = db.import_fs(&FsStore::new(cfg))?;
+    log::info!("import into sqlite store: {:?}", &report);
+    println!(
+        "imported {} bank(s), {} history record(s) and {} review deck(s) into {:?}, skipped {} existing",
+        report.banks,
+        report.history,
+        report.srs,
+        cfg.db_path(),
+        report.skipped
+    );
+    Ok(())
+}
+
+fn upgrade_packs(cfg: &Config, store: &dyn Store) -> Result<()> {
+    for (key, outcome) in crate::install_packs(cfg, store, true)? {
+        println!("{}: {:?}", key, outcome);
+    }
+    Ok(())
+}
+
+fn export_pack(
+    cfg: &Config,
+    store: &dyn Store,
+    file: &str,
+    typ: &str,
+    args: &[&str],
+) -> Result<()> {
+    let mut manifest = Manifest {
+        name: std::path::Path::new(file)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        version: String::from("1"),
+        typ: typ.to_string(),
+        author: String::new(),
+    };
+    let mut names = Vec::new();
+    for arg in args {
+        match arg.split_once('=') {
+            Some(("version", v)) => manifest.version = v.to_string(),
+            Some(("author", v)) => manifest.author = v.to_string(),
+            _ => names.push(arg.to_string()),
+        }
+    }
+    let pack = Pack::export(cfg, store, manifest, &names)?;
+    std::fs::write(file, ron::ser::to_string(&pack)?)?;
+    println!(
+        "exported {} bank(s) and {} media file(s) into {}",
+        pack.banks.len(),
+        pack.media.len(),
+        file
+    );
+    Ok(())
+}
+
+fn import_pack(cfg: &Config, store: &dyn Store, file: &str) -> Result<()> {
+    crate::expect_packs(cfg, store);
+    let pack: Pack = ron::de::from_str(&std::fs::read_to_string(file)?)?;
+    let report = pack.import(cfg, store)?;
+    println!(
+        "imported {} bank(s) and {} media file(s) from pack {} {}, kept {} existing: {:?}",
+        report.banks,
+        report.media,
+        &pack.manifest.name,
+        &pack.manifest.version,
+        report.skipped.len(),
+        &report.skipped
+    );
+    Ok(())
+}
+
+fn lint(store: &dyn Store) -> Result<()> {
+    let (mut banks, mut errors) = (0, 0);
+    for typ in ["1", "2", "3", "4"] {
+        for name in store.list_banks(typ)? {
+            banks += 1;
+            let diags = match crate::validate_bank(store, typ, &name) {
+                Ok(v) => v,
+                Err(e) => {
+                    errors += 1;
+                    println!("{}/{}: [Error] {}", typ, &name, e);
+                    continue;
+                }
+            };
+            for d in diags {
+                if d.severity() == Severity::Error {
+                    errors += 1;
+                }
+                println!("{}/{}: [{:?}] {}", typ, &name, d.severity(), d);
+            }
+        }
+    }
+    println!("checked {} bank(s), {} error(s)", banks, errors);
+    if errors > 0 {
+        return Err(format!("{} error(s) found", errors).into());
+    }
+    Ok(())
+}
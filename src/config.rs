@@ -0,0 +1,230 @@
+use serde_derive::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_ROOT: &str = "/var/lifeich1/elearn";
+const ENV_ROOT: &str = "ELEARN_ROOT";
+const ENV_PORT: &str = "ELEARN_PORT";
+const ENV_CONFIG: &str = "ELEARN_CONFIG";
+const ENV_STORE: &str = "ELEARN_STORE";
+const ENV_SESSION_TTL: &str = "ELEARN_SESSION_TTL";
+/// 作答中试卷的默认保留时长（秒）
+const DEFAULT_SESSION_TTL: u64 = 24 * 3600;
+
+/// 存储后端
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    /// 数据根目录下的 RON/HTML 文件
+    #[default]
+    Fs,
+    /// 仅在内存中，进程退出即丢失
+    Memory,
+    /// 数据根目录下的 SQLite 数据库
+    Sqlite,
+}
+
+impl std::str::FromStr for StoreKind {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s {
+            "fs" => Ok(Self::Fs),
+            "memory" => Ok(Self::Memory),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(format!("unknown store kind {}", s).into()),
+        }
+    }
+}
+
+/// 运行配置
+///
+/// 优先级（高到低）：命令行参数、环境变量、配置文件、默认值。
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 数据根目录，`data/`、`log4rs.yml` 等都放在其下
+    pub root: PathBuf,
+    /// 监听端口，`None` 时由各服务使用自己的默认端口
+    pub port: Option<u16>,
+    /// 存储后端
+    pub store: StoreKind,
+    /// 作答中试卷无活动多久后过期
+    pub session_ttl: Duration,
+    /// 非 `--` 开头的位置参数，即子命令及其参数
+    pub command: Vec<String>,
+}
+
+/// 配置文件内容（RON），所有字段可选
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ConfigFile {
+    root: Option<PathBuf>,
+    port: Option<u16>,
+    editor_port: Option<u16>,
+    store: Option<StoreKind>,
+    /// 秒
+    session_ttl: Option<u64>,
+}
+
+/// 命令行参数
+#[derive(Debug, Default)]
+struct Args {
+    root: Option<PathBuf>,
+    port: Option<u16>,
+    config: Option<PathBuf>,
+    store: Option<StoreKind>,
+    session_ttl: Option<u64>,
+    command: Vec<String>,
+}
+
+impl Args {
+    fn parse<I: Iterator<Item = String>>(mut it: I) -> crate::Result<Self> {
+        let mut args = Self::default();
+        while let Some(a) = it.next() {
+            if !a.starts_with("--") {
+                args.command.push(a);
+                continue;
+            }
+            let (key, inline) = match a.split_once('=') {
+                Some((k, v)) => (k.to_string(), Some(v.to_string())),
+                None => (a, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| it.next())
+                    .ok_or_else(|| crate::Error::from(format!("missing value of {}", key)))
+            };
+            match key.as_str() {
+                "--root" => args.root = Some(value()?.into()),
+                "--config" => args.config = Some(value()?.into()),
+                "--port" => args.port = Some(parse_port(&value()?)?),
+                "--store" => args.store = Some(value()?.parse()?),
+                "--session-ttl" => args.session_ttl = Some(parse_secs(&value()?)?),
+                _ => return Err(format!("unknown argument {}", key).into()),
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn parse_port(s: &str) -> crate::Result<u16> {
+    s.parse()
+        .map_err(|e| format!("invalid port {}: {}", s, e).into())
+}
+
+fn parse_secs(s: &str) -> crate::Result<u64> {
+    s.parse()
+        .map_err(|e| format!("invalid seconds {}: {}", s, e).into())
+}
+
+fn default_config_file() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+        .map(|d| d.join("elearn/config.ron"))
+}
+
+fn load_config_file(explicit: Option<PathBuf>) -> crate::Result<ConfigFile> {
+    let explicit = explicit.or_else(|| std::env::var_os(ENV_CONFIG).map(PathBuf::from));
+    let path = match explicit {
+        Some(p) => p,
+        None => match default_config_file() {
+            Some(p) if p.exists() => p,
+            _ => return Ok(Default::default()),
+        },
+    };
+    let input = std::fs::File::open(&path)
+        .map_err(|e| format!("open config file {:?} error: {}", &path, e))?;
+    Ok(ron::de::from_reader(input)?)
+}
+
+impl Config {
+    /// 按优先级解析配置；`editor` 决定取配置文件中的哪个端口
+    pub fn load<I: Iterator<Item = String>>(args: I, editor: bool) -> crate::Result<Self> {
+        let args = Args::parse(args)?;
+        let file = load_config_file(args.config)?;
+        let env_port = match std::env::var(ENV_PORT) {
+            Ok(s) => Some(parse_port(&s)?),
+            Err(_) => None,
+        };
+        let env_store = match std::env::var(ENV_STORE) {
+            Ok(s) => Some(s.parse()?),
+            Err(_) => None,
+        };
+        let env_ttl = match std::env::var(ENV_SESSION_TTL) {
+            Ok(s) => Some(parse_secs(&s)?),
+            Err(_) => None,
+        };
+        let file_port = if editor { file.editor_port } else { file.port };
+        Ok(Self {
+            root: args
+                .root
+                .or_else(|| std::env::var_os(ENV_ROOT).map(PathBuf::from))
+                .or(file.root)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_ROOT)),
+            port: args.port.or(env_port).or(file_port),
+            store: args.store.or(env_store).or(file.store).unwrap_or_default(),
+            session_ttl: Duration::from_secs(
+                args.session_ttl
+                    .or(env_ttl)
+                    .or(file.session_ttl)
+                    .unwrap_or(DEFAULT_SESSION_TTL),
+            ),
+            command: args.command,
+        })
+    }
+
+    /// 从进程参数与环境解析配置
+    pub fn from_env(editor: bool) -> crate::Result<Self> {
+        Self::load(std::env::args().skip(1), editor)
+    }
+
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            port: None,
+            store: Default::default(),
+            session_ttl: Duration::from_secs(DEFAULT_SESSION_TTL),
+            command: Vec::new(),
+        }
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.root.join("log4rs.yml")
+    }
+
+    pub fn static_dir(&self) -> PathBuf {
+        self.root.join("data/static")
+    }
+
+    pub fn test_dir(&self) -> PathBuf {
+        self.root.join("data/test")
+    }
+
+    pub fn history_root(&self) -> PathBuf {
+        self.root.join("data/history")
+    }
+
+    pub fn session_dir(&self) -> PathBuf {
+        self.root.join("data/session")
+    }
+
+    pub fn srs_dir(&self) -> PathBuf {
+        self.root.join("data/srs")
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        self.root.join("data/elearn.db")
+    }
+
+    /// 题库包附带的媒体文件，按包名分目录
+    pub fn media_dir(&self) -> PathBuf {
+        self.root.join("data/media")
+    }
+
+    /// 已安装内置题库的记录
+    pub fn packs_path(&self) -> PathBuf {
+        self.root.join("data/packs.ron")
+    }
+}
@@ -1,11 +1,18 @@
+use crate::{ErrorKind, Result};
+use error_chain::bail;
 use rand::seq::{IteratorRandom, SliceRandom};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 /// 聚类型测试数据
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct ClusteringExam {
     pub data: Vec<ClusteringItem>,
+    #[serde(default)]
+    pub policy: ExamPolicy,
+    /// 默写时视为相同的拼写，每组互为异写，如 `["shi", "si"]`
+    #[serde(default)]
+    pub spellings: Vec<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -14,6 +21,9 @@ pub struct ClusteringItem {
     pub data: String,
     /// 聚类标识
     pub clsid: i32,
+    /// 标签，区分同一聚类中的不同写法，如 `hiragana`、`man'yogana`
+    #[serde(default)]
+    pub kind: Option<String>,
 }
 
 impl ClusteringExam {
@@ -41,28 +51,379 @@ impl ClusteringExam {
         m.values().cloned().collect()
     }
 
-    pub fn gen_probs(&self, count: usize) -> Vec<ClusteringExamProb> {
+    /// 检查题库内容，列出所有问题；含 `Severity::Error` 时无法正常出题
+    pub fn validate(&self) -> Vec<Diagnostic> {
         if self.data.is_empty() {
-            return Default::default();
+            return vec![Diagnostic::EmptyBank];
         }
-        let mut heads: Vec<ClusteringItem> = Vec::new();
-        let mut rng = &mut rand::thread_rng();
-        while heads.len() < count {
-            let c: usize = self.data.len().min(count.wrapping_sub(heads.len()));
-            heads.extend(self.data.as_slice().choose_multiple(&mut rng, c).cloned());
+        let mut v = Vec::new();
+        let mut seen: BTreeMap<&str, BTreeSet<i32>> = BTreeMap::new();
+        for (index, item) in self.data.iter().enumerate() {
+            if item.data.trim().is_empty() {
+                v.push(Diagnostic::EmptyData { index });
+                continue;
+            }
+            if !seen.entry(&item.data).or_default().insert(item.clsid) {
+                v.push(Diagnostic::DuplicateData {
+                    data: item.data.clone(),
+                    clsid: item.clsid,
+                });
+            }
+        }
+        v.extend(
+            seen.iter()
+                .filter(|(_, clsids)| clsids.len() > 1)
+                .map(|(data, clsids)| Diagnostic::AmbiguousData {
+                    data: data.to_string(),
+                    clsids: clsids.iter().cloned().collect(),
+                }),
+        );
+        let table = self.table();
+        v.extend(
+            table
+                .iter()
+                .filter(|c| {
+                    c.iter()
+                        .map(|i| i.data.as_str())
+                        .filter(|d| !d.trim().is_empty())
+                        .collect::<HashSet<_>>()
+                        .len()
+                        < 2
+                })
+                .map(|c| Diagnostic::SingletonCluster { clsid: c[0].clsid }),
+        );
+        let need = GenOptions::default().option_count;
+        if table.len() < need {
+            v.push(Diagnostic::TooFewClusters {
+                count: table.len(),
+                need,
+            });
+        }
+        v
+    }
+
+    /// 题库中的全部标签，不重复
+    pub fn kinds(&self) -> BTreeSet<&str> {
+        self.data.iter().filter_map(|i| i.kind.as_deref()).collect()
+    }
+
+    /// 按 `opts` 可作题干的项
+    fn heads(&self, opts: &GenOptions) -> Vec<ClusteringItem> {
+        self.data
+            .iter()
+            .filter(|i| opts.is_head(i))
+            .cloned()
+            .collect()
+    }
+
+    /// 按 `opts` 可作选项的项
+    fn options<'a>(&'a self, opts: &'a GenOptions) -> impl Iterator<Item = &'a ClusteringItem> {
+        self.data.iter().filter(|i| opts.is_option(i))
+    }
+
+    /// 检查能否按 `opts` 出题，列出成员过少的聚类
+    pub fn check(&self, opts: &GenOptions) -> Result<()> {
+        if opts.option_count < 2 {
+            bail!(ErrorKind::BadOptionCount(opts.option_count));
+        }
+        if self.data.is_empty() {
+            bail!(ErrorKind::EmptyBank);
+        }
+        let heads = self.heads(opts);
+        if heads.is_empty() {
+            bail!(ErrorKind::NoItemOfKinds(opts.head_kinds.clone()));
+        }
+        // 题干所在的聚类中须有不同表示的选项可作答案
+        let small: BTreeSet<i32> = heads
+            .iter()
+            .filter(|h| {
+                !self
+                    .options(opts)
+                    .any(|i| i.clsid == h.clsid && i.data != h.data)
+            })
+            .map(|h| h.clsid)
+            .collect();
+        if !small.is_empty() {
+            bail!(ErrorKind::ClusterTooSmall(small.into_iter().collect()));
+        }
+        let need = opts.option_count - 1;
+        let clsids: BTreeSet<i32> = heads.iter().map(|h| h.clsid).collect();
+        let sparse: Vec<i32> = clsids
+            .into_iter()
+            .filter(|c| self.options(opts).filter(|i| i.clsid != *c).count() < need)
+            .collect();
+        if !sparse.is_empty() {
+            bail!(ErrorKind::TooFewDistractors(sparse, need));
+        }
+        Ok(())
+    }
+
+    /// 以给定种子出题；题库不变时同一种子总得到同一份试卷
+    pub fn gen_probs_seeded(
+        &self,
+        count: usize,
+        opts: &GenOptions,
+        seed: u64,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.gen_probs_with_rng(count, opts, &mut seeded_rng(seed))
+    }
+
+    pub fn gen_probs_with_rng<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        opts: &GenOptions,
+        mut rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.check(opts)?;
+        let pool = self.heads(opts);
+        let heads = sample_heads(&pool, count, &mut rng);
+        self.gen_probs_for_heads(&heads, opts, None, rng)
+    }
+
+    /// 按 `weights` 加权抽取题干出题，每轮不重复
+    pub fn gen_probs_weighted<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        opts: &GenOptions,
+        weights: &dyn Weights,
+        mut rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.check(opts)?;
+        let pool = self.heads(opts);
+        let heads = sample_heads_weighted(&pool, count, |i| weights.head(i), &mut rng)?;
+        self.gen_probs_for_heads(&heads, opts, Some(weights), rng)
+    }
+
+    /// 以给定的题干依次出题，`weights` 为 `None` 时均匀抽取干扰项
+    pub fn gen_probs_for_heads<R: ?Sized + rand::Rng>(
+        &self,
+        heads: &[ClusteringItem],
+        opts: &GenOptions,
+        weights: Option<&dyn Weights>,
+        mut rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.check(opts)?;
+        heads
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                Ok(self
+                    .gen_prob(&mut rng, opts, weights, h)?
+                    .id_changed(i as i32))
+            })
+            .collect()
+    }
+
+    /// 以给定种子出默写题：只给题干，同一聚类中可作选项的表示及其异写都算正确
+    pub fn gen_probs_typed<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        opts: &GenOptions,
+        mut rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        let pool = self.heads(opts);
+        if pool.is_empty() {
+            bail!(ErrorKind::NoItemOfKinds(opts.head_kinds.clone()));
         }
+        let heads = sample_heads(&pool, count, &mut rng);
         heads
             .iter()
             .enumerate()
-            .map(|(i, h)| self.gen_prob(&mut rng, h).id_changed(i as i32))
+            .map(|(i, head)| {
+                let mut accepted: Vec<String> = Vec::new();
+                for item in self
+                    .options(opts)
+                    .filter(|i| i.clsid == head.clsid && i.data != head.data)
+                {
+                    let group = self
+                        .spellings
+                        .iter()
+                        .find(|g| g.iter().any(|s| normalize(s) == normalize(&item.data)));
+                    for s in std::iter::once(&item.data).chain(group.into_iter().flatten()) {
+                        if !accepted.iter().any(|a| normalize(a) == normalize(s)) {
+                            accepted.push(s.clone());
+                        }
+                    }
+                }
+                if accepted.is_empty() {
+                    bail!(ErrorKind::ClusterTooSmall(vec![head.clsid]));
+                }
+                Ok(ClusteringExamProb {
+                    id: i as i32,
+                    answer: 0,
+                    head: head.data.clone(),
+                    explain: format!("可接受的答案：{}", accepted.join(" ")),
+                    opts: accepted
+                        .iter()
+                        .enumerate()
+                        .map(|(id, html)| ClusteringExamProbOption {
+                            id: id as i32,
+                            html: html.clone(),
+                        })
+                        .collect(),
+                    ..Default::default()
+                })
+            })
             .collect()
     }
 
+    /// 出多选题：每题有1至选项数个与题干同类的选项，其余为其他聚类的干扰项
+    pub fn gen_probs_multi<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        opts: &GenOptions,
+        mut rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.check(opts)?;
+        let pool = self.heads(opts);
+        let heads = sample_heads(&pool, count, &mut rng);
+        let size = opts.option_count;
+        heads
+            .iter()
+            .enumerate()
+            .map(|(i, head)| {
+                let mut seen = HashSet::new();
+                let sames: Vec<&ClusteringItem> = self
+                    .options(opts)
+                    .filter(|i| i.clsid == head.clsid && i.data != head.data)
+                    .filter(|i| seen.insert(i.data.as_str()))
+                    .collect();
+                if sames.is_empty() {
+                    bail!(ErrorKind::ClusterTooSmall(vec![head.clsid]));
+                }
+                let k = rng.gen_range(1..=sames.len().min(size));
+                let mut items: Vec<(bool, &ClusteringItem)> = sames
+                    .choose_multiple(&mut rng, k)
+                    .map(|i| (true, *i))
+                    .collect();
+                items.extend(
+                    self.options(opts)
+                        .filter(|i| i.clsid != head.clsid)
+                        .choose_multiple(&mut rng, size - k)
+                        .into_iter()
+                        .map(|i| (false, i)),
+                );
+                if items.len() < size {
+                    bail!(ErrorKind::TooFewDistractors(vec![head.clsid], size - k));
+                }
+                items.shuffle(&mut rng);
+                let answers: Vec<i32> = (0..items.len() as i32)
+                    .filter(|n| items[*n as usize].0)
+                    .collect();
+                Ok(ClusteringExamProb {
+                    id: i as i32,
+                    answer: answers[0],
+                    answers,
+                    head: head.data.clone(),
+                    explain: format!(
+                        "以下字符同类：{}",
+                        sames
+                            .iter()
+                            .map(|i| i.data.as_str())
+                            .collect::<Vec<&str>>()
+                            .join(" ")
+                    ),
+                    opts: items
+                        .iter()
+                        .enumerate()
+                        .map(|(id, (_, i))| ClusteringExamProbOption {
+                            id: id as i32,
+                            html: i.data.clone(),
+                        })
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+
+    /// 出 `count` 组连线题，每组取 `opts.option_count` 个不同聚类的题干与目标
+    pub fn gen_matching<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        opts: &GenOptions,
+        mut rng: &mut R,
+    ) -> Result<Vec<MatchingProb>> {
+        let size = opts.option_count;
+        if size < 2 {
+            bail!(ErrorKind::BadOptionCount(size));
+        }
+        let heads = self.heads(opts);
+        if heads.is_empty() {
+            bail!(ErrorKind::NoItemOfKinds(opts.head_kinds.clone()));
+        }
+        // 聚类 -> (可作题干的项, 可作目标的项)
+        let mut clusters: BTreeMap<i32, (Vec<&ClusteringItem>, Vec<&ClusteringItem>)> =
+            BTreeMap::new();
+        for h in heads.iter() {
+            clusters.entry(h.clsid).or_default().0.push(h);
+        }
+        for i in self.options(opts) {
+            if let Some(c) = clusters.get_mut(&i.clsid) {
+                c.1.push(i);
+            }
+        }
+        let usable: Vec<i32> = clusters
+            .iter()
+            .filter(|(_, (hs, ts))| hs.iter().any(|h| ts.iter().any(|t| t.data != h.data)))
+            .map(|(c, _)| *c)
+            .collect();
+        if usable.len() < size {
+            bail!(ErrorKind::TooFewClusters(usable.len(), size));
+        }
+        let mut v = Vec::new();
+        for n in 0..count {
+            let mut heads = Vec::new();
+            let mut targets = Vec::new();
+            for c in usable.choose_multiple(&mut rng, size) {
+                let (hs, ts) = &clusters[c];
+                let (h, t) = hs
+                    .iter()
+                    .flat_map(|h| {
+                        ts.iter()
+                            .filter(|t| t.data != h.data)
+                            .map(move |t| (*h, *t))
+                    })
+                    .choose(&mut rng)
+                    .ok_or_else(|| ErrorKind::ClusterTooSmall(vec![*c]))?;
+                heads.push(h.data.clone());
+                targets.push(t.data.clone());
+            }
+            let mut order: Vec<usize> = (0..size).collect();
+            order.shuffle(&mut rng);
+            let mut answers = vec![0; size];
+            for (pos, i) in order.iter().enumerate() {
+                answers[*i] = pos as i32;
+            }
+            v.push(MatchingProb {
+                id: (n * size) as i32,
+                explain: heads
+                    .iter()
+                    .zip(targets.iter())
+                    .map(|(h, t)| format!("{} - {}", h, t))
+                    .collect::<Vec<String>>()
+                    .join("；"),
+                targets: order
+                    .iter()
+                    .enumerate()
+                    .map(|(id, i)| ClusteringExamProbOption {
+                        id: id as i32,
+                        html: targets[*i].clone(),
+                    })
+                    .collect(),
+                heads,
+                answers,
+            });
+        }
+        Ok(v)
+    }
+
     fn gen_prob<R: ?Sized + rand::Rng>(
         &self,
         mut rng: &mut R,
+        opts: &GenOptions,
+        weights: Option<&dyn Weights>,
         head: &ClusteringItem,
-    ) -> ClusteringExamProb {
+    ) -> Result<ClusteringExamProb> {
         let sames: Vec<ClusteringItem> = self
             .data
             .iter()
@@ -70,30 +431,35 @@ impl ClusteringExam {
             .cloned()
             .collect();
         let answer = sames
-            .as_slice()
-            .choose_multiple(&mut rng, 2)
-            .filter(|i| i.data != head.data)
-            .take(1)
-            .cloned();
-        let mut opts: Vec<ClusteringItem> = self
-            .data
-            .iter()
-            .filter(|i| i.clsid != head.clsid)
-            .choose_multiple(&mut rng, 3)
             .iter()
+            .filter(|i| i.data != head.data && opts.is_option(i))
+            .choose(&mut rng)
             .cloned()
-            .cloned()
-            .collect();
-        opts.extend(answer);
-        opts.as_mut_slice().shuffle(&mut rng);
-        ClusteringExamProb {
-            opts: ClusteringExamProbOption::opts_from_items(&opts[..]),
-            answer: opts
-                .iter()
-                .enumerate()
-                .find(|(_, val)| val.clsid == head.clsid)
-                .expect("Should find answer in options")
-                .0 as i32,
+            .ok_or_else(|| ErrorKind::ClusterTooSmall(vec![head.clsid]))?;
+        let need = opts.option_count - 1;
+        let others = self.options(opts).filter(|i| i.clsid != head.clsid);
+        let mut items: Vec<ClusteringItem> = match weights {
+            None => others
+                .choose_multiple(&mut rng, need)
+                .into_iter()
+                .cloned()
+                .collect(),
+            Some(w) => others
+                .collect::<Vec<&ClusteringItem>>()
+                .choose_multiple_weighted(&mut rng, need, |i| w.distractor(head, i))
+                .map_err(|e| format!("weighted sampling error: {}", e))?
+                .map(|i| (*i).clone())
+                .collect(),
+        };
+        if items.len() < need {
+            bail!(ErrorKind::TooFewDistractors(vec![head.clsid], need));
+        }
+        items.as_mut_slice().shuffle(&mut rng);
+        let pos = rng.gen_range(0..=items.len());
+        items.insert(pos, answer);
+        Ok(ClusteringExamProb {
+            opts: ClusteringExamProbOption::opts_from_items(&items[..]),
+            answer: pos as i32,
             head: head.data.clone(),
             explain: format!(
                 "以下字符同类：{}",
@@ -102,8 +468,778 @@ impl ClusteringExam {
                     .fold(String::new(), |res, i| format!("{} {}", res, &i.data))
             ),
             ..Default::default()
+        })
+    }
+}
+
+/// 有限集有向路型测试数据：每项带若干命名的面，按“源面→目标面”出题
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FacetExam {
+    /// 面的名称，如 `romaji`、`hiragana`、`katakana`
+    pub facets: Vec<String>,
+    pub items: Vec<FacetItem>,
+    #[serde(default)]
+    pub policy: ExamPolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FacetItem {
+    /// 各面的表示，与 `FacetExam::facets` 一一对应，空串表示缺失
+    pub values: Vec<String>,
+}
+
+/// 有向路型试卷的出题方向
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Direction {
+    pub from: String,
+    pub to: String,
+}
+
+impl FacetExam {
+    fn facet_index(&self, name: &str) -> Result<usize> {
+        self.facets
+            .iter()
+            .position(|f| f == name)
+            .ok_or_else(|| ErrorKind::UnknownFacet(name.to_string()).into())
+    }
+
+    /// 源面与目标面都有表示的 `(源, 目标)`，不重复
+    fn pairs(&self, dir: &Direction) -> Result<Vec<(&str, &str)>> {
+        let (from, to) = (self.facet_index(&dir.from)?, self.facet_index(&dir.to)?);
+        if from == to {
+            bail!(ErrorKind::BadDirection(dir.from.clone(), dir.to.clone()));
+        }
+        let mut seen = HashSet::new();
+        Ok(self
+            .items
+            .iter()
+            .filter_map(|i| {
+                let src = i.values.get(from).map(|s| s.trim()).unwrap_or_default();
+                let dst = i.values.get(to).map(|s| s.trim()).unwrap_or_default();
+                (!src.is_empty() && !dst.is_empty()).then_some((src, dst))
+            })
+            .filter(|p| seen.insert(*p))
+            .collect())
+    }
+
+    /// 检查题库内容，诊断同 `ClusteringExam::validate`
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        if self.items.is_empty() {
+            return vec![Diagnostic::EmptyBank];
+        }
+        let mut v = Vec::new();
+        if self.facets.len() < 2 {
+            v.push(Diagnostic::TooFewFacets {
+                count: self.facets.len(),
+            });
+        }
+        let mut names = HashSet::new();
+        for (index, f) in self.facets.iter().enumerate() {
+            if f.trim().is_empty() {
+                v.push(Diagnostic::EmptyFacet { index });
+            } else if !names.insert(f.as_str()) {
+                v.push(Diagnostic::DuplicateFacet { name: f.clone() });
+            }
+        }
+        v.extend(
+            self.items
+                .iter()
+                .enumerate()
+                .filter(|(_, i)| i.values.iter().all(|s| s.trim().is_empty()))
+                .map(|(index, _)| Diagnostic::EmptyData { index }),
+        );
+        v
+    }
+
+    /// 检查能否按方向 `dir` 与 `opts` 出题
+    pub fn check(&self, dir: &Direction, opts: &GenOptions) -> Result<()> {
+        if opts.option_count < 2 {
+            bail!(ErrorKind::BadOptionCount(opts.option_count));
+        }
+        let pairs = self.pairs(dir)?;
+        let targets: HashSet<&str> = pairs.iter().map(|p| p.1).collect();
+        if targets.len() < opts.option_count {
+            bail!(ErrorKind::TooFewItems(targets.len(), opts.option_count));
+        }
+        Ok(())
+    }
+
+    /// 以给定种子按方向 `dir` 出题
+    pub fn gen_probs_seeded(
+        &self,
+        count: usize,
+        dir: &Direction,
+        opts: &GenOptions,
+        seed: u64,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.gen_probs_with_rng(count, dir, opts, &mut seeded_rng(seed))
+    }
+
+    pub fn gen_probs_with_rng<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        dir: &Direction,
+        opts: &GenOptions,
+        mut rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.check(dir, opts)?;
+        let pairs = self.pairs(dir)?;
+        // 同一源可能对应多个目标（如 o → お、を），都算正确，不能作为干扰项
+        let mut answers: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (src, dst) in pairs.iter() {
+            answers.entry(src).or_default().push(dst);
+        }
+        let mut targets: Vec<&str> = pairs.iter().map(|p| p.1).collect();
+        targets.sort_unstable();
+        targets.dedup();
+        let heads = sample_heads(&pairs, count, &mut rng);
+        heads
+            .iter()
+            .enumerate()
+            .map(|(i, (src, dst))| {
+                let need = opts.option_count - 1;
+                let mut items: Vec<&str> = targets
+                    .iter()
+                    .filter(|t| !answers[src].contains(t))
+                    .choose_multiple(&mut rng, need)
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                if items.len() < need {
+                    bail!(ErrorKind::TooFewItems(items.len() + 1, opts.option_count));
+                }
+                items.as_mut_slice().shuffle(&mut rng);
+                let pos = rng.gen_range(0..=items.len());
+                items.insert(pos, dst);
+                Ok(ClusteringExamProb {
+                    id: i as i32,
+                    answer: pos as i32,
+                    head: src.to_string(),
+                    explain: format!(
+                        "{}“{}”对应的{}：{}",
+                        &dir.from,
+                        src,
+                        &dir.to,
+                        answers[src].join(" ")
+                    ),
+                    opts: items
+                        .iter()
+                        .enumerate()
+                        .map(|(id, html)| ClusteringExamProbOption {
+                            id: id as i32,
+                            html: html.to_string(),
+                        })
+                        .collect(),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+/// 按“源面→目标面”出题的题库
+pub trait FacetBank {
+    fn policy(&self) -> ExamPolicy;
+    /// 题库诊断，同 `validate`
+    fn diagnose(&self) -> Vec<Diagnostic>;
+    /// 以给定种子按方向 `dir` 出题
+    fn gen_directed(
+        &self,
+        count: usize,
+        dir: &Direction,
+        opts: &GenOptions,
+        seed: u64,
+    ) -> Result<Vec<ClusteringExamProb>>;
+}
+
+impl FacetBank for FacetExam {
+    fn policy(&self) -> ExamPolicy {
+        self.policy
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        self.validate()
+    }
+
+    fn gen_directed(
+        &self,
+        count: usize,
+        dir: &Direction,
+        opts: &GenOptions,
+        seed: u64,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.gen_probs_seeded(count, dir, opts, seed)
+    }
+}
+
+/// 六十四卦型题库的面：卦画、卦名、卦辞
+pub const HEXAGRAM_FACETS: [&str; 3] = ["卦画", "卦名", "卦辞"];
+
+/// 六十四卦型测试数据：卦画、卦名与卦辞两两对应
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HexagramExam {
+    pub hexagrams: Vec<Hexagram>,
+    #[serde(default)]
+    pub policy: ExamPolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Hexagram {
+    /// 自初爻至上爻的六爻，`1` 为阳爻、`0` 为阴爻，如屯为 `100010`
+    pub lines: String,
+    pub name: String,
+    /// 卦辞
+    pub judgement: String,
+}
+
+impl Hexagram {
+    fn values(&self) -> [&str; 3] {
+        [self.lines.trim(), self.name.trim(), self.judgement.trim()]
+    }
+}
+
+fn is_hexagram_lines(s: &str) -> bool {
+    s.len() == 6 && s.bytes().all(|b| b == b'0' || b == b'1')
+}
+
+/// 卦画的内联 SVG，自下而上为初爻至上爻；`lines` 不是六位 `0`/`1` 时原样转义
+pub fn hexagram_svg(lines: &str) -> String {
+    if !is_hexagram_lines(lines) {
+        return escape_html(lines);
+    }
+    let mut s = format!(
+        r#"<svg class="hexagram" xmlns="http://www.w3.org/2000/svg" width="36" height="33" viewBox="0 0 36 33" role="img" aria-label="{}">"#,
+        lines
+    );
+    for (i, b) in lines.bytes().enumerate() {
+        let y = (5 - i) * 6;
+        if b == b'1' {
+            s += &format!(
+                r#"<rect x="0" y="{}" width="36" height="3" fill="currentColor"/>"#,
+                y
+            );
+        } else {
+            s += &format!(
+                r#"<rect x="0" y="{0}" width="15" height="3" fill="currentColor"/><rect x="21" y="{0}" width="15" height="3" fill="currentColor"/>"#,
+                y
+            );
         }
     }
+    s + "</svg>"
+}
+
+/// 转义文本以嵌入选项的 html
+fn escape_html(s: &str) -> String {
+    s.chars()
+        .fold(String::with_capacity(s.len()), |mut acc, c| {
+            match c {
+                '&' => acc.push_str("&amp;"),
+                '<' => acc.push_str("&lt;"),
+                '>' => acc.push_str("&gt;"),
+                '"' => acc.push_str("&quot;"),
+                '\'' => acc.push_str("&#x27;"),
+                _ => acc.push(c),
+            }
+            acc
+        })
+}
+
+impl HexagramExam {
+    /// 以卦画、卦名、卦辞为面的有向路型题库
+    fn facets(&self) -> FacetExam {
+        FacetExam {
+            facets: HEXAGRAM_FACETS.iter().map(|f| f.to_string()).collect(),
+            items: self
+                .hexagrams
+                .iter()
+                .map(|h| FacetItem {
+                    values: h.values().iter().map(|v| v.to_string()).collect(),
+                })
+                .collect(),
+            policy: self.policy,
+        }
+    }
+
+    /// 检查题库内容，诊断同 `ClusteringExam::validate`
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut v = self.facets().validate();
+        v.extend(
+            self.hexagrams
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| !is_hexagram_lines(h.lines.trim()))
+                .map(|(index, _)| Diagnostic::BadLines { index }),
+        );
+        v
+    }
+
+    /// 按方向 `dir` 出题：卦画画成 SVG，其余转义为 html，解析给出卦名与卦辞
+    pub fn gen_probs_with_rng<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        dir: &Direction,
+        opts: &GenOptions,
+        rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        let bank = self.facets();
+        let (from, to) = (bank.facet_index(&dir.from)?, bank.facet_index(&dir.to)?);
+        let render = |facet: usize, s: &str| match facet {
+            0 => hexagram_svg(s),
+            _ => escape_html(s),
+        };
+        Ok(bank
+            .gen_probs_with_rng(count, dir, opts, rng)?
+            .into_iter()
+            .map(|mut p| {
+                if let Some(h) = self.hexagrams.iter().find(|h| h.values()[from] == p.head) {
+                    p.explain = format!("{}：{}", h.name.trim(), h.judgement.trim());
+                }
+                p.head = render(from, &p.head);
+                for o in p.opts.iter_mut() {
+                    o.html = render(to, &o.html);
+                }
+                p
+            })
+            .collect())
+    }
+}
+
+impl FacetBank for HexagramExam {
+    fn policy(&self) -> ExamPolicy {
+        self.policy
+    }
+
+    fn diagnose(&self) -> Vec<Diagnostic> {
+        self.validate()
+    }
+
+    fn gen_directed(
+        &self,
+        count: usize,
+        dir: &Direction,
+        opts: &GenOptions,
+        seed: u64,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        self.gen_probs_with_rng(count, dir, opts, &mut seeded_rng(seed))
+    }
+}
+
+/// 序列型测试数据：每项是一个有序的列表，如五十音的行序、文王六十四卦序
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SequenceExam {
+    pub sequences: Vec<Sequence>,
+    #[serde(default)]
+    pub policy: ExamPolicy,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Sequence {
+    pub name: String,
+    /// 按先后排列的各项
+    pub items: Vec<String>,
+}
+
+impl SequenceExam {
+    /// 名为 `name` 的序列
+    pub fn sequence(&self, name: &str) -> Result<&Sequence> {
+        self.sequences
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| ErrorKind::UnknownSequence(name.to_string()).into())
+    }
+
+    /// 检查题库内容，诊断同 `ClusteringExam::validate`
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        if self.sequences.iter().all(|s| s.items.is_empty()) {
+            return vec![Diagnostic::EmptyBank];
+        }
+        let mut v = Vec::new();
+        let mut names = HashSet::new();
+        for (index, s) in self.sequences.iter().enumerate() {
+            if s.name.trim().is_empty() {
+                v.push(Diagnostic::EmptySequenceName { index });
+            } else if !names.insert(s.name.as_str()) {
+                v.push(Diagnostic::DuplicateSequence {
+                    name: s.name.clone(),
+                });
+            }
+            if s.items.len() < 2 {
+                v.push(Diagnostic::ShortSequence {
+                    name: s.name.clone(),
+                });
+            }
+            let mut seen = HashSet::new();
+            for (index, item) in s.items.iter().enumerate() {
+                if item.trim().is_empty() {
+                    v.push(Diagnostic::EmptyStep {
+                        name: s.name.clone(),
+                        index,
+                    });
+                } else if !seen.insert(item.trim()) {
+                    v.push(Diagnostic::DuplicateStep {
+                        name: s.name.clone(),
+                        data: item.clone(),
+                    });
+                }
+            }
+        }
+        v
+    }
+
+    /// 检查能否按 `opts` 从序列 `name` 出题：选项与排序的项都取自同一序列
+    pub fn check(&self, name: &str, opts: &GenOptions) -> Result<&Sequence> {
+        if opts.option_count < 2 {
+            bail!(ErrorKind::BadOptionCount(opts.option_count));
+        }
+        let seq = self.sequence(name)?;
+        if seq.items.len() < opts.option_count {
+            bail!(ErrorKind::TooFewItems(seq.items.len(), opts.option_count));
+        }
+        Ok(seq)
+    }
+
+    /// 从序列 `name` 出 `count` 道单选题，问某项的下一项；`next` 为假时问前一项
+    pub fn gen_probs_adjacent<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        name: &str,
+        next: bool,
+        opts: &GenOptions,
+        mut rng: &mut R,
+    ) -> Result<Vec<ClusteringExamProb>> {
+        let seq = self.check(name, opts)?;
+        // (题干, 答案) 的下标
+        let steps: Vec<(usize, usize)> = (1..seq.items.len())
+            .map(|i| if next { (i - 1, i) } else { (i, i - 1) })
+            .collect();
+        let heads = sample_heads(&steps, count, &mut rng);
+        let word = if next { "下一项" } else { "前一项" };
+        Ok(heads
+            .iter()
+            .enumerate()
+            .map(|(i, (head, answer))| {
+                let mut items: Vec<&str> = (0..seq.items.len())
+                    .filter(|n| n != answer)
+                    .choose_multiple(&mut rng, opts.option_count - 1)
+                    .into_iter()
+                    .map(|n| seq.items[n].as_str())
+                    .collect();
+                items.as_mut_slice().shuffle(&mut rng);
+                let pos = rng.gen_range(0..=items.len());
+                items.insert(pos, &seq.items[*answer]);
+                ClusteringExamProb {
+                    id: i as i32,
+                    answer: pos as i32,
+                    head: seq.items[*head].clone(),
+                    explain: format!(
+                        "{}中“{}”的{}是“{}”",
+                        &seq.name, &seq.items[*head], word, &seq.items[*answer]
+                    ),
+                    opts: items
+                        .iter()
+                        .enumerate()
+                        .map(|(id, html)| ClusteringExamProbOption {
+                            id: id as i32,
+                            html: html.to_string(),
+                        })
+                        .collect(),
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+
+    /// 从序列 `name` 出 `count` 组排序题，每组取 `opts.option_count` 项打乱，为各项连上其位次
+    pub fn gen_order<R: ?Sized + rand::Rng>(
+        &self,
+        count: usize,
+        name: &str,
+        opts: &GenOptions,
+        mut rng: &mut R,
+    ) -> Result<Vec<MatchingProb>> {
+        let seq = self.check(name, opts)?;
+        let size = opts.option_count;
+        let targets: Vec<ClusteringExamProbOption> = (0..size)
+            .map(|id| ClusteringExamProbOption {
+                id: id as i32,
+                html: format!("第{}位", id + 1),
+            })
+            .collect();
+        Ok((0..count)
+            .map(|n| {
+                let mut picked: Vec<usize> = (0..seq.items.len()).choose_multiple(&mut rng, size);
+                picked.sort_unstable();
+                let mut order: Vec<usize> = (0..size).collect();
+                order.shuffle(&mut rng);
+                MatchingProb {
+                    id: (n * size) as i32,
+                    heads: order
+                        .iter()
+                        .map(|i| seq.items[picked[*i]].clone())
+                        .collect(),
+                    targets: targets.clone(),
+                    answers: order.iter().map(|i| *i as i32).collect(),
+                    explain: format!(
+                        "{}中的顺序：{}",
+                        &seq.name,
+                        picked
+                            .iter()
+                            .map(|i| seq.items[*i].as_str())
+                            .collect::<Vec<&str>>()
+                            .join(" → ")
+                    ),
+                }
+            })
+            .collect())
+    }
+}
+
+/// 出卷方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExamMode {
+    /// 从题库中随机抽题
+    #[default]
+    Random,
+    /// 按复习进度优先抽到期的题干
+    Review,
+    /// 按过往错误率加权抽题干，并偏向常混淆的干扰项
+    Weak,
+    /// 随机抽题干，输入答案而非选择
+    Typed,
+    /// 连线：每组若干题干与目标两两配对
+    Matching,
+    /// 多选：选出所有与题干同类的选项，按比例得分
+    Multi,
+    /// 序列型：问某项的下一项
+    Next,
+    /// 序列型：问某项的前一项
+    Prev,
+    /// 序列型：为打乱的若干项排出先后
+    Order,
+}
+
+/// 默写答案的规范形式：全角 ASCII 转半角，去掉首尾空白并合并中间的空白，转小写
+pub fn normalize(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            _ => c,
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// 诊断的严重程度
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// 题库诊断
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// 题库为空
+    EmptyBank,
+    /// 第 `index` 项的表示为空
+    EmptyData { index: usize },
+    /// 同一表示出现在多个聚类中，题目会有歧义
+    AmbiguousData { data: String, clsids: Vec<i32> },
+    /// 同一聚类中重复的表示
+    DuplicateData { data: String, clsid: i32 },
+    /// 聚类中不同的表示少于2个，无法作为题干
+    SingletonCluster { clsid: i32 },
+    /// 聚类数少于默认选项数，干扰项只能来自少数聚类
+    TooFewClusters { count: usize, need: usize },
+    /// 有向路型题库的面少于2个
+    TooFewFacets { count: usize },
+    /// 第 `index` 个面的名称为空
+    EmptyFacet { index: usize },
+    /// 重名的面
+    DuplicateFacet { name: String },
+    /// 第 `index` 个序列的名称为空
+    EmptySequenceName { index: usize },
+    /// 重名的序列
+    DuplicateSequence { name: String },
+    /// 序列不足2项，无法出题
+    ShortSequence { name: String },
+    /// 序列 `name` 的第 `index` 项为空
+    EmptyStep { name: String, index: usize },
+    /// 同一序列中重复的项，前后项会有歧义
+    DuplicateStep { name: String, data: String },
+    /// 第 `index` 卦的卦画不是六位 `0`/`1`
+    BadLines { index: usize },
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::DuplicateData { .. } => Severity::Warning,
+            Self::TooFewClusters { count, .. } if *count >= 2 => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::EmptyBank => write!(f, "题库为空"),
+            Self::EmptyData { index } => write!(f, "第{}项为空", index + 1),
+            Self::AmbiguousData { data, clsids } => {
+                write!(f, "“{}”同时属于聚类{:?}", data, clsids)
+            }
+            Self::DuplicateData { data, clsid } => {
+                write!(f, "聚类{}中“{}”重复", clsid, data)
+            }
+            Self::SingletonCluster { clsid } => {
+                write!(f, "聚类{}的不同成员少于2个", clsid)
+            }
+            Self::TooFewClusters { count, need } => {
+                write!(f, "只有{}个聚类，少于{}个", count, need)
+            }
+            Self::TooFewFacets { count } => write!(f, "只有{}个面，少于2个", count),
+            Self::EmptyFacet { index } => write!(f, "第{}个面的名称为空", index + 1),
+            Self::DuplicateFacet { name } => write!(f, "面“{}”重名", name),
+            Self::EmptySequenceName { index } => write!(f, "第{}个序列的名称为空", index + 1),
+            Self::DuplicateSequence { name } => write!(f, "序列“{}”重名", name),
+            Self::ShortSequence { name } => write!(f, "序列“{}”少于2项", name),
+            Self::EmptyStep { name, index } => {
+                write!(f, "序列“{}”的第{}项为空", name, index + 1)
+            }
+            Self::DuplicateStep { name, data } => {
+                write!(f, "序列“{}”中“{}”重复", name, data)
+            }
+            Self::BadLines { index } => write!(f, "第{}卦的卦画应为六位0/1", index + 1),
+        }
+    }
+}
+
+/// 题干与干扰项的抽样权重，须为正数
+pub trait Weights {
+    fn head(&self, item: &ClusteringItem) -> f64;
+    /// 以 `item` 作为题干 `head` 的干扰项的权重
+    fn distractor(&self, head: &ClusteringItem, item: &ClusteringItem) -> f64;
+}
+
+/// 题库的测试规则，出卷时随试卷保存并由服务端执行
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct ExamPolicy {
+    /// 每卷题数
+    pub prob_count: usize,
+    /// 错题数达到此值即提前结束，0 表示不限
+    pub max_wrong: usize,
+    /// 整卷限时（秒），0 表示不限
+    pub time_limit_secs: u64,
+    /// 每题限时（秒），0 表示不限
+    pub question_limit_secs: u64,
+    /// 是否允许放弃作答
+    pub allow_surrender: bool,
+}
+
+impl Default for ExamPolicy {
+    fn default() -> Self {
+        Self {
+            prob_count: 100,
+            max_wrong: 10,
+            time_limit_secs: 0,
+            question_limit_secs: 0,
+            allow_surrender: true,
+        }
+    }
+}
+
+/// 出题参数
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct GenOptions {
+    /// 每题选项数，含1个答案
+    pub option_count: usize,
+    /// 题干限于这些标签，空为不限
+    pub head_kinds: Vec<String>,
+    /// 答案与干扰项限于这些标签，空为不限
+    pub option_kinds: Vec<String>,
+}
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        Self {
+            option_count: 4,
+            head_kinds: Vec::new(),
+            option_kinds: Vec::new(),
+        }
+    }
+}
+
+impl GenOptions {
+    fn admits(kinds: &[String], item: &ClusteringItem) -> bool {
+        kinds.is_empty() || item.kind.as_ref().is_some_and(|k| kinds.contains(k))
+    }
+
+    /// `item` 能否作题干
+    pub fn is_head(&self, item: &ClusteringItem) -> bool {
+        Self::admits(&self.head_kinds, item)
+    }
+
+    /// `item` 能否作答案或干扰项
+    pub fn is_option(&self, item: &ClusteringItem) -> bool {
+        Self::admits(&self.option_kinds, item)
+    }
+}
+
+/// 由种子构造的随机数发生器
+///
+/// 使用 ChaCha8 而非 `StdRng`，后者的算法不保证跨 rand 版本稳定。
+pub fn seeded_rng(seed: u64) -> rand_chacha::ChaCha8Rng {
+    rand::SeedableRng::seed_from_u64(seed)
+}
+
+/// 从 `pool` 抽取 `count` 个题干：每轮不重复地抽，抽完整个 `pool` 再开始下一轮
+pub fn sample_heads<T: Clone, R: ?Sized + rand::Rng>(
+    pool: &[T],
+    count: usize,
+    rng: &mut R,
+) -> Vec<T> {
+    let mut heads = Vec::with_capacity(count);
+    while heads.len() < count && !pool.is_empty() {
+        let c = pool.len().min(count - heads.len());
+        heads.extend(pool.choose_multiple(rng, c).cloned());
+    }
+    heads
+}
+
+/// 同 `sample_heads`，每轮按 `weight` 加权抽取
+pub fn sample_heads_weighted<T: Clone, R: ?Sized + rand::Rng>(
+    pool: &[T],
+    count: usize,
+    weight: impl Fn(&T) -> f64,
+    rng: &mut R,
+) -> Result<Vec<T>> {
+    let mut heads = Vec::with_capacity(count);
+    while heads.len() < count && !pool.is_empty() {
+        let c = pool.len().min(count - heads.len());
+        heads.extend(
+            pool.choose_multiple_weighted(rng, c, &weight)
+                .map_err(|e| format!("weighted sampling error: {}", e))?
+                .cloned(),
+        );
+    }
+    Ok(heads)
+}
+
+/// 新试卷的随机种子
+pub fn new_seed() -> u64 {
+    rand::random()
+}
+
+/// 新试卷的随机id
+pub fn new_paper_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
 }
 
 /// 聚类型测试题目数据
@@ -111,6 +1247,9 @@ impl ClusteringExam {
 pub struct ClusteringExamProb {
     pub id: i32,
     pub answer: i32,
+    /// 多选题的全部正确选项，其他题型为空
+    #[serde(default)]
+    pub answers: Vec<i32>,
     pub head: String,
     pub explain: String,
     pub opts: Vec<ClusteringExamProbOption>,
@@ -139,3 +1278,151 @@ impl ClusteringExamProbOption {
             .collect()
     }
 }
+
+/// 连线题：`heads` 与打乱的 `targets` 两两配对
+///
+/// 每对按一道单选题判分与记录，第 `i` 对的题号为 `id + i`，选项为全部目标。
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MatchingProb {
+    /// 第一对的题号
+    pub id: i32,
+    pub heads: Vec<String>,
+    pub targets: Vec<ClusteringExamProbOption>,
+    /// 各题干对应的目标 id
+    pub answers: Vec<i32>,
+    pub explain: String,
+}
+
+impl MatchingProb {
+    /// 拆成逐对的单选题
+    pub fn pairs(&self) -> Vec<ClusteringExamProb> {
+        self.heads
+            .iter()
+            .zip(self.answers.iter())
+            .enumerate()
+            .map(|(i, (head, answer))| ClusteringExamProb {
+                id: self.id + i as i32,
+                answer: *answer,
+                head: head.clone(),
+                explain: self.explain.clone(),
+                opts: self.targets.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// 提前结束测试的原因
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// 错题数达到上限
+    TooManyWrong,
+    /// 超过整卷限时
+    TimeUp,
+}
+
+/// 单题作答结果
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProbResult {
+    pub id: i32,
+    pub head: String,
+    /// 各选项的 html，下标即选项 id
+    pub opts: Vec<String>,
+    /// 所选选项，`None` 表示放弃
+    pub chosen: Option<i32>,
+    pub answer: i32,
+    pub explain: String,
+    /// 作答用时（毫秒）
+    pub elapsed_ms: u64,
+    /// 服务端观测的作答延迟（毫秒），即下发本题到收到作答的间隔
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// 超过限时才作答，按超时判错
+    #[serde(default)]
+    pub timed_out: bool,
+    /// 默写题的输入原文，`opts` 为可接受的答案
+    #[serde(default)]
+    pub typed: Option<String>,
+    /// 多选题所选的选项
+    #[serde(default)]
+    pub picks: Option<Vec<i32>>,
+    /// 多选题的全部正确选项，其他题型为空
+    #[serde(default)]
+    pub answers: Vec<i32>,
+}
+
+impl ProbResult {
+    fn is_multi(&self) -> bool {
+        !self.answers.is_empty()
+    }
+
+    /// 多选题须恰好选出全部正确选项
+    pub fn is_correct(&self) -> bool {
+        match &self.picks {
+            _ if self.timed_out => false,
+            Some(picks) if self.is_multi() => {
+                picks.iter().collect::<BTreeSet<_>>() == self.answers.iter().collect()
+            }
+            _ if self.is_multi() => false,
+            _ => self.chosen == Some(self.answer),
+        }
+    }
+
+    /// 未作答：放弃或超时
+    pub fn is_surrendered(&self) -> bool {
+        self.chosen.is_none() && self.typed.is_none() && self.picks.is_none()
+    }
+
+    /// 本题得分，0~1；多选题为选中的正确选项数减去错选数，占正确选项数的比例
+    pub fn credit(&self) -> f64 {
+        match &self.picks {
+            Some(picks) if self.is_multi() && !self.timed_out => {
+                let hits = picks.iter().filter(|p| self.answers.contains(p)).count() as f64;
+                let misses = picks.len() as f64 - hits;
+                ((hits - misses) / self.answers.len() as f64).max(0.0)
+            }
+            _ if self.is_multi() => 0.0,
+            _ => self.is_correct() as u8 as f64,
+        }
+    }
+}
+
+/// 一次测试的结构化结果
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExamResult {
+    pub paper_id: String,
+    /// 出题种子，旧记录没有
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// 试卷题量，旧记录为 0
+    #[serde(default)]
+    pub prob_count: usize,
+    /// 出题参数
+    #[serde(default)]
+    pub gen: GenOptions,
+    #[serde(default)]
+    pub mode: ExamMode,
+    /// 有向路型试卷的出题方向
+    #[serde(default)]
+    pub direction: Option<Direction>,
+    /// 序列型试卷所考的序列
+    #[serde(default)]
+    pub sequence: Option<String>,
+    /// 已作答的题目，按题号升序
+    pub probs: Vec<ProbResult>,
+    /// 总用时（毫秒）
+    pub time_taken_ms: u64,
+    /// 全对的题数
+    pub score: usize,
+    /// 各题得分之和，多选题按比例计分
+    #[serde(default)]
+    pub credit: f64,
+    /// 提前结束的原因，`None` 表示答完全部题目
+    pub early_stop: Option<StopReason>,
+}
+
+impl ExamResult {
+    pub fn wrong_count(&self) -> usize {
+        self.probs.iter().filter(|p| !p.is_correct()).count()
+    }
+}
@@ -1,9 +1,11 @@
-use error_chain::error_chain;
+use error_chain::{bail, error_chain};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 
 error_chain! {
@@ -12,141 +14,146 @@ error_chain! {
         InitLog(log::SetLoggerError);
         ConfigLog(log4rs::config::runtime::ConfigErrors);
         Ron(ron::error::Error);
+        Sqlite(rusqlite::Error);
+    }
+
+    errors {
+        EmptyBank {
+            description("empty bank")
+            display("bank has no item")
+        }
+        BadOptionCount(n: usize) {
+            description("bad option count")
+            display("option count {} is less than 2", n)
+        }
+        ClusterTooSmall(clsids: Vec<i32>) {
+            description("cluster too small")
+            display("cluster(s) {:?} have fewer than 2 distinct items", clsids)
+        }
+        TooFewClusters(have: usize, need: usize) {
+            description("too few clusters")
+            display("only {} usable cluster(s), {} needed", have, need)
+        }
+        NoItemOfKinds(kinds: Vec<String>) {
+            description("no item of kinds")
+            display("no item tagged with any of {:?}", kinds)
+        }
+        UnknownFacet(name: String) {
+            description("unknown facet")
+            display("facet {:?} not found in bank", name)
+        }
+        BadDirection(from: String, to: String) {
+            description("bad direction")
+            display("cannot map facet {:?} to {:?}", from, to)
+        }
+        TooFewItems(have: usize, need: usize) {
+            description("too few items")
+            display("only {} distinct target(s), {} needed", have, need)
+        }
+        UnknownSequence(name: String) {
+            description("unknown sequence")
+            display("sequence {:?} not found in bank", name)
+        }
+        TooFewDistractors(clsids: Vec<i32>, need: usize) {
+            description("too few distractors")
+            display("cluster(s) {:?} have fewer than {} items outside to pick as distractors", clsids, need)
+        }
     }
 }
 
-macro_rules! var_path {
-    () => {
-        "/var/lifeich1/elearn"
-    };
-    (@log) => {
-        concat!(var_path!(), "/log4rs.yml")
-    };
-    (@static) => {
-        concat!(var_path!(), "/data/static")
+/// 以题库类型 `typ` 对应的数据类型为 `$V` 求值 `$body`
+macro_rules! by_bank_type {
+    ($typ:expr, $V:ident => $body:expr) => {
+        match $typ {
+            "1" => {
+                type $V = $crate::exam::ClusteringExam;
+                $body
+            }
+            "2" => {
+                type $V = $crate::exam::FacetExam;
+                $body
+            }
+            "3" => {
+                type $V = $crate::exam::SequenceExam;
+                $body
+            }
+            "4" => {
+                type $V = $crate::exam::HexagramExam;
+                $body
+            }
+            typ => ::error_chain::bail!("unknown test type {}", typ),
+        }
     };
 }
 
+mod cli;
+mod config;
 mod exam;
+mod pack;
+mod session;
+mod srs;
+mod stats;
+mod store;
+mod table;
 mod www;
 
-const VAR_PATH: &str = var_path!();
-const LOGYML_PATH: &str = var_path!(@log);
-
-fn test_data_path<T: ToString, U: ToString>(typ: T, name: U) -> String {
-    format!(
-        "{}/data/test/{}/{}.ron",
-        VAR_PATH,
-        typ.to_string(),
-        name.to_string()
-    )
-}
+pub use config::{Config, StoreKind};
+pub use store::{FsStore, ImportReport, MemStore, SharedStore, SqliteStore, Store};
 
-fn commit_test_data<T: ToString, U: ToString, V: Serialize>(
-    typ: T,
-    name: U,
+fn commit_test_data<V: Serialize>(
+    store: &dyn Store,
+    typ: &str,
+    name: &str,
     value: &V,
 ) -> Result<()> {
-    let out = File::create(test_data_path(typ, name))?;
-    ron::ser::to_writer(out, value)?;
-    Ok(())
+    store.save_bank(typ, name, &ron::ser::to_string(value)?)
 }
 
-fn load_test_data<T: ToString, U: ToString, V: DeserializeOwned>(typ: T, name: U) -> Result<V> {
-    let p = test_data_path(typ, name);
-    log::info!("opening {}", &p);
-    let input = File::open(p)?;
-    Ok(ron::de::from_reader(input)?)
+fn load_test_data<V: DeserializeOwned>(store: &dyn Store, typ: &str, name: &str) -> Result<V> {
+    Ok(ron::de::from_str(&store.load_bank(typ, name)?)?)
 }
 
-fn history_root() -> String {
-    format!("{}/data/history", VAR_PATH)
+/// 按题库类型加载并检查题库 `typ`/`name`
+fn validate_bank(store: &dyn Store, typ: &str, name: &str) -> Result<Vec<exam::Diagnostic>> {
+    diagnose_bank(typ, &store.load_bank(typ, name)?)
 }
 
-fn history_dir_path<T: ToString, U: ToString>(typ: T, name: U) -> String {
-    let s = format!(
-        "{}/{}/{}",
-        history_root(),
-        typ.to_string(),
-        name.to_string()
-    );
-    let p = Path::new(&s);
-    if !p.exists() {
-        std::fs::create_dir_all(p)
-            .unwrap_or_else(|e| panic!("Create test history dir {:?} error: {}", p, e));
-    }
-    s
+/// 按题库类型解析 RON 内容 `data` 并检查题库
+fn diagnose_bank(typ: &str, data: &str) -> Result<Vec<exam::Diagnostic>> {
+    Ok(by_bank_type!(typ, V => ron::de::from_str::<V>(data)?.validate()))
 }
 
-fn history_path<T: ToString, U: ToString>(typ: T, name: U, tag: &str) -> String {
-    format!("{}/{}.html", history_dir_path(typ, name), tag)
+/// 按题库类型解析编辑器提交的 JSON 并保存为题库 `typ`/`name`，返回题库的问题
+fn submit_bank(
+    store: &dyn Store,
+    typ: &str,
+    name: &str,
+    data: &[u8],
+) -> Result<Vec<exam::Diagnostic>> {
+    by_bank_type!(typ, V => {
+        let bank: V = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+        commit_test_data(store, typ, name, &bank)?;
+        Ok(bank.validate())
+    })
 }
 
-fn commit_history<T: ToString, U: ToString>(typ: T, name: U, data: &str) -> Result<()> {
-    let tag = chrono::Local::now().format("%F_%Hh%Mm%Ss").to_string();
-    log::info!(
-        "commiting new history {}/{}/{}",
-        typ.to_string(),
-        name.to_string(),
-        &tag
-    );
-    let mut out = File::create(history_path(typ, name, &tag))?;
-    write!(&mut out, "{}", data)?;
-    Ok(())
+/// 按题库类型读出题库 `typ`/`name` 的 RON 内容
+fn export_bank(store: &dyn Store, typ: &str, name: &str) -> Result<String> {
+    by_bank_type!(typ, V => Ok(ron::ser::to_string(&load_test_data::<V>(store, typ, name)?)?))
 }
 
-fn list_history_of_kind<T: ToString, U: ToString>(typ: T, name: U) -> Vec<String> {
-    let s = history_dir_path(typ, name);
-    let p = Path::new(&s);
-    let d = p
-        .read_dir()
-        .unwrap_or_else(|e| panic!("Open test history dir {:?} error: {}", p, e));
-    d.filter_map(|result| match result {
-        Ok(entry) => entry
-            .file_name()
-            .into_string()
-            .map_err(|e| log::error!("history name {:?} cannot convert to utf8", e))
-            .ok()
-            .and_then(|s| s.strip_suffix(".html").map(String::from)),
-        Err(e) => {
-            log::error!("read dir {:?} error: {}", p, e);
-            None
-        }
-    })
-    .collect()
+/// 按题库类型解析题库内容，重新序列化为 `commit_test_data` 保存的格式
+fn canonical_bank(typ: &str, data: &str) -> Result<String> {
+    by_bank_type!(typ, V => canonical::<V>(data))
 }
 
-fn list_names_of_test_type<T: ToString>(typ: T) -> Vec<String> {
-    let datapath = test_data_path(typ.to_string(), "none");
-    let p = Path::new(&datapath)
-        .parent()
-        .unwrap_or_else(|| panic!("Cannot get dir of test type {}", typ.to_string()));
-    if !p.exists() {
-        std::fs::create_dir_all(p)
-            .unwrap_or_else(|e| panic!("Create test type data dir {:?} error: {}", p, e));
-    }
-    let d = p
-        .read_dir()
-        .unwrap_or_else(|e| panic!("Open test type data dir {:?} error: {}", p, e));
-    d.filter_map(|result| match result {
-        Ok(entry) => entry
-            .file_name()
-            .into_string()
-            .map_err(|e| {
-                log::error!(
-                    "test name {:?} of test type {} cannot to utf string",
-                    e,
-                    typ.to_string()
-                )
-            })
-            .ok()
-            .and_then(|s| s.strip_suffix(".ron").map(String::from)),
-        Err(e) => {
-            log::error!("read dir {:?} error: {}", p, e);
-            None
-        }
+/// 按配置打开存储后端
+pub fn open_store(cfg: &Config) -> Result<SharedStore> {
+    Ok(match cfg.store {
+        StoreKind::Fs => SharedStore::new(FsStore::new(cfg)),
+        StoreKind::Memory => SharedStore::new(MemStore::new()),
+        StoreKind::Sqlite => SharedStore::new(SqliteStore::open(cfg)?),
     })
-    .collect()
 }
 
 fn b_dump2file<P: AsRef<Path>>(path: P, data: &[u8], is_force: bool) -> Result<()> {
@@ -179,14 +186,165 @@ macro_rules! flat_extracts {
     };
 }
 
-fn prepare_log<T: ToString>(tag: T) -> Result<()> {
+/// 随程序发布的内置题库，安装为 `data/test/<typ>/<name>.ron`
+struct Pack {
+    typ: &'static str,
+    name: &'static str,
+    data: &'static str,
+    /// 按题库类型解析并重新序列化，与编辑器保存的格式一致
+    canonical: fn(&str) -> Result<String>,
+}
+
+fn canonical<V: DeserializeOwned + Serialize>(data: &str) -> Result<String> {
+    Ok(ron::ser::to_string(&ron::de::from_str::<V>(data)?)?)
+}
+
+macro_rules! bundled_packs {
+    ($($typ:literal, $name:literal, $bank:ty;)*) => {
+        &[$(Pack {
+            typ: $typ,
+            name: $name,
+            data: include_str!(concat!("../assets/", $name, ".ron")),
+            canonical: canonical::<$bank>,
+        },)*]
+    };
+}
+
+const PACKS: &[Pack] = bundled_packs!(
+    "1", "日文假名", exam::ClusteringExam;
+    "4", "周易六十四卦", exam::HexagramExam;
+);
+
+/// 安装内置题库的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackOutcome {
+    /// 题库不存在，已安装
+    Installed,
+    /// 安装后未经编辑，已换成新版
+    Upgraded,
+    /// 与内置版本相同
+    UpToDate,
+    /// 已被编辑或是同名的其他题库，保留不动
+    KeptEdits,
+    /// 安装后被删除，不再安装
+    Deleted,
+}
+
+/// `data/packs.ron` 中的安装记录
+#[derive(serde_derive::Serialize, serde_derive::Deserialize, Debug, Default)]
+struct PackRecords {
+    /// `<typ>/<name>` -> 安装时内容的摘要
+    installed: BTreeMap<String, u64>,
+    /// 安装后被用户删除的内置题库
+    #[serde(default)]
+    deleted: std::collections::BTreeSet<String>,
+}
+
+impl PackRecords {
+    fn load(cfg: &Config) -> Result<Self> {
+        let s = match std::fs::read_to_string(cfg.packs_path()) {
+            Ok(s) => s,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(e) => return Err(e.into()),
+        };
+        // 早期的记录只有摘要表
+        Ok(match ron::de::from_str(&s) {
+            Ok(v) => v,
+            Err(_) => Self {
+                installed: ron::de::from_str(&s)?,
+                ..Default::default()
+            },
+        })
+    }
+
+    fn save(&self, cfg: &Config) -> Result<()> {
+        b_dump2file(
+            cfg.packs_path(),
+            ron::ser::to_string(self)?.as_bytes(),
+            true,
+        )
+    }
+}
+
+/// 题库内容的摘要（FNV-1a），用于判断安装后是否被编辑过
+fn digest(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// 安装存储中缺少的内置题库；`upgrade` 时把安装后未经编辑的旧版换成内置版本，
+/// 并重新安装被删除的内置题库
+///
+/// 安装时内容的摘要记在 `data/packs.ron`，据此判断题库是否被编辑过；被删除的
+/// 内置题库也记在其中，启动时不再安装。
+pub fn install_packs(
+    cfg: &Config,
+    store: &dyn Store,
+    upgrade: bool,
+) -> Result<Vec<(String, PackOutcome)>> {
+    let mut records = PackRecords::load(cfg)?;
+    let mut v = Vec::new();
+    for p in PACKS {
+        let key = format!("{}/{}", p.typ, p.name);
+        let bundled = (p.canonical)(p.data)?;
+        let current = match store.list_banks(p.typ)?.iter().any(|n| n == p.name) {
+            true => Some(store.load_bank(p.typ, p.name)?),
+            false => None,
+        };
+        let outcome = match current {
+            None if !upgrade && records.deleted.contains(&key) => PackOutcome::Deleted,
+            None => PackOutcome::Installed,
+            Some(cur) if cur == bundled => PackOutcome::UpToDate,
+            Some(cur) if upgrade && records.installed.get(&key) == Some(&digest(&cur)) => {
+                PackOutcome::Upgraded
+            }
+            Some(_) => PackOutcome::KeptEdits,
+        };
+        if matches!(outcome, PackOutcome::Installed | PackOutcome::Upgraded) {
+            store.save_bank(p.typ, p.name, &bundled)?;
+            log::info!("install bundled bank {}: {:?}", &key, outcome);
+        }
+        if matches!(outcome, PackOutcome::Installed | PackOutcome::Upgraded) {
+            records.deleted.remove(&key);
+        }
+        if !matches!(outcome, PackOutcome::KeptEdits | PackOutcome::Deleted) {
+            records.installed.insert(key.clone(), digest(&bundled));
+        }
+        v.push((key, outcome));
+    }
+    records.save(cfg)?;
+    Ok(v)
+}
+
+/// 记下被删除的题库 `typ`/`name`；是内置题库时启动不再安装
+pub fn forget_pack(cfg: &Config, typ: &str, name: &str) -> Result<()> {
+    if !PACKS.iter().any(|p| p.typ == typ && p.name == name) {
+        return Ok(());
+    }
+    let mut records = PackRecords::load(cfg)?;
+    records.deleted.insert(format!("{}/{}", typ, name));
+    records.save(cfg)
+}
+
+/// 启动时安装缺少的内置题库，出错只记日志
+fn expect_packs(cfg: &Config, store: &dyn Store) {
+    if let Err(e) = install_packs(cfg, store, false) {
+        log::error!("install bundled banks error: {}", e);
+    }
+}
+
+fn prepare_log<T: ToString>(cfg: &Config, tag: T) -> Result<()> {
+    let logyml = cfg.log_path();
     b_dump2file(
-        Path::new(LOGYML_PATH),
-        include_bytes!("../assets/log4rs.yml"),
+        &logyml,
+        include_str!("../assets/log4rs.yml")
+            .replace("/var/lifeich1/elearn", &cfg.root.to_string_lossy())
+            .as_bytes(),
         false,
     )?;
 
-    log4rs::init_file(LOGYML_PATH, Default::default())
+    log4rs::init_file(&logyml, Default::default())
         .unwrap_or_else(|e| panic!("prepare log fatal error(s): {}", e));
 
     log::info!(
@@ -197,7 +355,7 @@ fn prepare_log<T: ToString>(tag: T) -> Result<()> {
     );
 
     flat_extracts!(
-        var_path!(@static), "../static";
+        &cfg.static_dir(), "../static";
         "editor_index.js",
         "index.js",
         "favicon.ico",
@@ -206,8 +364,8 @@ fn prepare_log<T: ToString>(tag: T) -> Result<()> {
     Ok(())
 }
 
-fn expect_log<T: ToString>(tag: T) {
-    prepare_log(tag).unwrap_or_else(|e| panic!("prepare log fatal error(s): {}", e));
+fn expect_log<T: ToString>(cfg: &Config, tag: T) {
+    prepare_log(cfg, tag).unwrap_or_else(|e| panic!("prepare log fatal error(s): {}", e));
 }
 
 macro_rules! graceful_shutdown {
@@ -226,7 +384,13 @@ macro_rules! graceful_shutdown {
 }
 
 pub async fn run_editor() -> Result<()> {
-    expect_log("editor");
+    let cfg = Arc::new(Config::from_env(true)?);
+    expect_log(&cfg, "editor");
+    let store = open_store(&cfg)?;
+    if !cfg.command.is_empty() {
+        return cli::run(&cfg, &*store);
+    }
+    expect_packs(&cfg, &*store);
 
     let (done_tx, mut done_rx) = mpsc::channel::<i32>(1);
     {
@@ -235,9 +399,9 @@ pub async fn run_editor() -> Result<()> {
         let done = done_tx.clone();
         tokio::spawn(async move {
             let _done = done;
-            www::run_editor(rx).await;
+            www::run_editor(rx, cfg, store).await;
         });
-        let _ = tokio::signal::ctrl_c().await?;
+        tokio::signal::ctrl_c().await?;
         log::info!("Caught ^C, quiting");
     }
     drop(done_tx);
@@ -249,7 +413,13 @@ pub async fn run_editor() -> Result<()> {
 }
 
 pub async fn run() -> Result<()> {
-    expect_log("elearn");
+    let cfg = Arc::new(Config::from_env(false)?);
+    if !cfg.command.is_empty() {
+        bail!("unexpected argument(s) {:?}", cfg.command);
+    }
+    expect_log(&cfg, "elearn");
+    let store = open_store(&cfg)?;
+    expect_packs(&cfg, &*store);
 
     let (done_tx, mut done_rx) = mpsc::channel::<i32>(1);
     {
@@ -258,9 +428,9 @@ pub async fn run() -> Result<()> {
         let done = done_tx.clone();
         tokio::spawn(async move {
             let _done = done;
-            www::run(rx).await;
+            www::run(rx, cfg, store).await;
         });
-        let _ = tokio::signal::ctrl_c().await?;
+        tokio::signal::ctrl_c().await?;
         log::info!("Caught ^C, quiting");
     }
     drop(done_tx);
@@ -0,0 +1,141 @@
+use crate::exam::Severity;
+use crate::store::check_name;
+use crate::{Config, Result, Store};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 题库包中允许的媒体文件扩展名：图片与音频
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "mp3", "ogg", "wav", "m4a",
+];
+
+/// 文件 `file` 是否为允许的媒体文件；网页、脚本等不能经 `/media` 提供
+pub fn is_media(file: &str) -> bool {
+    std::path::Path::new(file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MEDIA_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// 题库包的清单
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    /// 包内题库的类型
+    pub typ: String,
+    pub author: String,
+}
+
+/// 题库包：清单、同一类型的若干题库与可选的媒体文件，整体存为一个 RON 文件
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Pack {
+    pub manifest: Manifest,
+    /// 题库名 -> 题库的 RON 内容
+    pub banks: BTreeMap<String, String>,
+    /// 媒体文件名 -> base64 编码的内容，导入到 `data/media/<包名>/`
+    #[serde(default)]
+    pub media: BTreeMap<String, String>,
+}
+
+/// `Pack::import` 的导入统计
+#[derive(Debug, Default)]
+pub struct PackReport {
+    pub banks: usize,
+    pub media: usize,
+    /// 已存在且内容不同、因而保留不动的题库
+    pub skipped: Vec<String>,
+}
+
+impl Pack {
+    /// 打包存储中类型为 `manifest.typ` 的题库 `names`，以及 `data/media/<包名>/` 下的文件
+    pub fn export(
+        cfg: &Config,
+        store: &dyn Store,
+        manifest: Manifest,
+        names: &[String],
+    ) -> Result<Self> {
+        check_name("pack", &manifest.name)?;
+        let mut banks = BTreeMap::new();
+        for name in names {
+            banks.insert(
+                name.clone(),
+                crate::export_bank(store, &manifest.typ, name)?,
+            );
+        }
+        let mut media = BTreeMap::new();
+        let dir = cfg.media_dir().join(&manifest.name);
+        if dir.is_dir() {
+            for e in std::fs::read_dir(&dir)? {
+                let e = e?;
+                let file = e.file_name().to_string_lossy().into_owned();
+                if e.file_type()?.is_file() && is_media(&file) {
+                    media.insert(file, base64::encode(std::fs::read(e.path())?));
+                }
+            }
+        }
+        Ok(Self {
+            manifest,
+            banks,
+            media,
+        })
+    }
+
+    /// 导入包内的题库与媒体文件；已存在且内容不同的题库保留不动
+    ///
+    /// 先解析并检查全部题库与媒体文件，任何题库有错误或含非媒体文件时不写入。
+    pub fn import(&self, cfg: &Config, store: &dyn Store) -> Result<PackReport> {
+        let typ = &self.manifest.typ;
+        check_name("pack", &self.manifest.name)?;
+        let mut banks = Vec::new();
+        for (name, data) in self.banks.iter() {
+            check_name("test", name)?;
+            let data = crate::canonical_bank(typ, data)
+                .map_err(|e| format!("bank {:?} in pack: {}", name, e))?;
+            let errors: Vec<String> = crate::diagnose_bank(typ, &data)?
+                .iter()
+                .filter(|d| d.severity() == Severity::Error)
+                .map(|d| d.to_string())
+                .collect();
+            if !errors.is_empty() {
+                return Err(format!("bank {:?} in pack: {}", name, errors.join("；")).into());
+            }
+            banks.push((name, data));
+        }
+        let mut media = Vec::new();
+        for (file, data) in self.media.iter() {
+            check_name("media", file)?;
+            if !is_media(file) {
+                return Err(
+                    format!("media {:?} in pack is not an image or audio file", file).into(),
+                );
+            }
+            let bytes =
+                base64::decode(data).map_err(|e| format!("media {:?} in pack: {}", file, e))?;
+            media.push((file, bytes));
+        }
+        let existing = store.list_banks(typ)?;
+        let mut report = PackReport::default();
+        for (name, data) in banks {
+            if existing.contains(name) && store.load_bank(typ, name)? != data {
+                report.skipped.push(name.clone());
+                continue;
+            }
+            store.save_bank(typ, name, &data)?;
+            report.banks += 1;
+        }
+        let dir = cfg.media_dir().join(&self.manifest.name);
+        for (file, bytes) in media {
+            crate::b_dump2file(dir.join(file), &bytes, true)?;
+            report.media += 1;
+        }
+        log::info!(
+            "import pack {} {} of type {}: {:?}",
+            &self.manifest.name,
+            &self.manifest.version,
+            typ,
+            &report
+        );
+        Ok(report)
+    }
+}
@@ -0,0 +1,577 @@
+use crate::exam::{
+    normalize, ClusteringExamProb, Direction, ExamMode, ExamPolicy, ExamResult, GenOptions,
+    MatchingProb, ProbResult, StopReason,
+};
+use crate::srs::Deck;
+use crate::{Result, SharedStore};
+use chrono::TimeZone;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// 判定超时前额外宽限的时间（毫秒），抵消揭晓答案的停顿与网络延迟
+const DEADLINE_GRACE_MS: i64 = 2000;
+
+pub fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// 已下发、作答中的试卷；答案只保存在服务端
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExamSession {
+    pub id: String,
+    pub typ: String,
+    pub name: String,
+    /// 出题种子，用于重现同一份试卷
+    #[serde(default)]
+    pub seed: u64,
+    /// 出题参数
+    #[serde(default)]
+    pub gen: GenOptions,
+    #[serde(default)]
+    pub mode: ExamMode,
+    /// 出卷时题库的测试规则
+    #[serde(default)]
+    pub policy: ExamPolicy,
+    /// 有向路型试卷的出题方向
+    #[serde(default)]
+    pub direction: Option<Direction>,
+    /// 序列型试卷所考的序列
+    #[serde(default)]
+    pub sequence: Option<String>,
+    pub probs: Vec<ClusteringExamProb>,
+    /// 连线卷的各组，`probs` 为其逐对拆开的单选题
+    #[serde(default)]
+    pub grids: Vec<MatchingProb>,
+    /// 已作答的题目，按题号升序
+    pub answers: Vec<ProbResult>,
+    /// 下发时间（Unix 毫秒）
+    pub created_ms: i64,
+    /// 上一次作答（或下发试卷）的时间（Unix 毫秒）
+    pub updated_ms: i64,
+    /// 当前题目的下发时间（Unix 毫秒），续答时重新计起；旧试卷为 0，取 `updated_ms`
+    #[serde(default)]
+    pub issued_ms: i64,
+}
+
+impl ExamSession {
+    fn wrong_count(&self) -> usize {
+        self.answers.iter().filter(|p| !p.is_correct()).count()
+    }
+
+    fn early_stop(&self) -> Option<StopReason> {
+        if self.answers.len() >= self.probs.len() {
+            None
+        } else if self.policy.max_wrong > 0 && self.wrong_count() >= self.policy.max_wrong {
+            Some(StopReason::TooManyWrong)
+        } else if self.is_time_up(self.updated_ms) {
+            Some(StopReason::TimeUp)
+        } else {
+            None
+        }
+    }
+
+    /// 整卷的截止时间（Unix 毫秒），不限时为 `None`
+    fn paper_deadline(&self) -> Option<i64> {
+        match self.policy.time_limit_secs {
+            0 => None,
+            s => Some(self.created_ms + s as i64 * 1000),
+        }
+    }
+
+    /// 当前题目的截止时间（Unix 毫秒），不限时为 `None`
+    fn question_deadline(&self) -> Option<i64> {
+        match self.policy.question_limit_secs {
+            0 => None,
+            s => Some(self.issued() + s as i64 * 1000),
+        }
+    }
+
+    /// 当前题目的下发时间（Unix 毫秒）
+    fn issued(&self) -> i64 {
+        self.issued_ms.max(self.updated_ms)
+    }
+
+    /// 到 `at_ms` 时是否已超过整卷限时
+    fn is_time_up(&self, at_ms: i64) -> bool {
+        self.paper_deadline().is_some_and(|d| at_ms > d)
+    }
+
+    /// 在 `at_ms` 收到的作答是否已超过整卷或本题的截止时间 `grace` 毫秒以上
+    fn is_late(&self, at_ms: i64, grace: i64) -> bool {
+        [self.paper_deadline(), self.question_deadline()]
+            .iter()
+            .flatten()
+            .any(|d| at_ms > d + grace)
+    }
+
+    /// 距整卷截止的剩余时间（毫秒），不限时为 `None`
+    pub fn remaining_ms(&self) -> Option<i64> {
+        self.paper_deadline().map(|d| (d - now_ms()).max(0))
+    }
+
+    /// 距本题截止的剩余时间（毫秒），不限时为 `None`
+    pub fn question_remaining_ms(&self) -> Option<i64> {
+        self.question_deadline().map(|d| (d - now_ms()).max(0))
+    }
+
+    /// 从第 `id` 题开始的连线题组
+    fn grid_at(&self, id: usize) -> Option<&MatchingProb> {
+        self.grids.iter().find(|g| g.id as usize == id)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.answers.len() >= self.probs.len() || self.early_stop().is_some()
+    }
+
+    fn result(&self) -> ExamResult {
+        ExamResult {
+            paper_id: self.id.clone(),
+            seed: Some(self.seed),
+            prob_count: self.probs.len(),
+            gen: self.gen.clone(),
+            mode: self.mode,
+            direction: self.direction.clone(),
+            sequence: self.sequence.clone(),
+            probs: self.answers.clone(),
+            time_taken_ms: (self.updated_ms - self.created_ms).max(0) as u64,
+            score: self.answers.len() - self.wrong_count(),
+            credit: self.credit(),
+            early_stop: self.early_stop(),
+        }
+    }
+
+    /// 已作答题目的得分之和
+    fn credit(&self) -> f64 {
+        self.answers.iter().map(ProbResult::credit).sum()
+    }
+
+    fn is_expired(&self, ttl: Duration) -> bool {
+        now_ms() - self.updated_ms > ttl.as_millis() as i64
+    }
+
+    fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            answered: self.answers.len(),
+            total: self.probs.len(),
+            score: self.answers.len() - self.wrong_count(),
+            mode: self.mode,
+            updated: chrono::Local
+                .timestamp_millis(self.updated_ms)
+                .format("%F %H:%M:%S")
+                .to_string(),
+        }
+    }
+}
+
+/// 新试卷的出卷参数
+#[derive(Debug, Clone)]
+pub struct PaperSpec {
+    pub mode: ExamMode,
+    /// 出题种子
+    pub seed: u64,
+    pub gen: GenOptions,
+    /// 题库的测试规则
+    pub policy: ExamPolicy,
+    /// 有向路型试卷的出题方向
+    pub direction: Option<Direction>,
+    /// 序列型试卷所考的序列
+    pub sequence: Option<String>,
+}
+
+/// 可继续作答的试卷概况，供欢迎页列出
+#[derive(Serialize, Debug)]
+pub struct SessionSummary {
+    pub id: String,
+    pub answered: usize,
+    pub total: usize,
+    pub score: usize,
+    pub mode: ExamMode,
+    /// 上次作答的本地时间
+    pub updated: String,
+}
+
+/// 客户端提交的单题作答
+#[derive(Deserialize, Debug)]
+pub struct Answer {
+    pub paper_id: String,
+    pub prob: i32,
+    /// 所选选项，`None` 表示放弃
+    pub chosen: Option<i32>,
+    /// 默写题的输入，`None` 表示放弃
+    #[serde(default)]
+    pub typed: Option<String>,
+    /// 连线题一组各题干所选的目标，`None` 表示未连
+    #[serde(default)]
+    pub pairs: Vec<Option<i32>>,
+    /// 多选题所选的选项，`None` 表示放弃
+    #[serde(default)]
+    pub picks: Option<Vec<i32>>,
+    /// 客户端计时（毫秒），不超过服务端观测到的间隔
+    pub elapsed_ms: u64,
+}
+
+/// 单题判分结果
+#[derive(Serialize, Debug)]
+pub struct Graded {
+    pub correct: bool,
+    pub answer: i32,
+    pub explain: String,
+    /// 本卷是否已结束
+    pub finished: bool,
+    pub score: usize,
+    pub wrong: usize,
+    /// 结束时保存测试记录的结果描述
+    pub saved: Option<String>,
+    /// 本题超时，按错判
+    pub timed_out: bool,
+    /// 距整卷截止的剩余时间（毫秒），不限时为 `None`
+    pub remaining_ms: Option<i64>,
+    /// 连线题逐对的判分，其他题型为空
+    pub pairs: Vec<PairGraded>,
+    /// 多选题的全部正确选项，其他题型为空
+    pub answers: Vec<i32>,
+    /// 本题得分，0~1；连线题为各对得分的平均
+    pub credit: f64,
+    /// 已作答题目的得分之和
+    pub total_credit: f64,
+}
+
+/// 连线题一对的判分
+#[derive(Serialize, Debug)]
+pub struct PairGraded {
+    pub correct: bool,
+    /// 正确的目标 id
+    pub answer: i32,
+}
+
+/// 作答中试卷的管理：下发、判分、续答与过期清理
+///
+/// 试卷经由 `Store` 持久化，服务重启后仍可继续作答。
+pub struct ExamSessions {
+    store: SharedStore,
+    ttl: Duration,
+    /// 串行化对存储的读改写
+    lock: Mutex<()>,
+}
+
+impl ExamSessions {
+    pub fn new(store: SharedStore, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 取得存储的锁；持锁时出过 panic 也照常取得，试卷以存储中的为准
+    fn guard(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn load(&self, id: &str) -> Result<ExamSession> {
+        Ok(ron::de::from_str(&self.store.load_paper(id)?)?)
+    }
+
+    fn save(&self, session: &ExamSession) -> Result<()> {
+        self.store
+            .save_paper(&session.id, &ron::ser::to_string(session)?)
+    }
+
+    /// 登记按 `spec` 生成的新试卷
+    pub fn open(
+        &self,
+        typ: &str,
+        name: &str,
+        spec: PaperSpec,
+        probs: Vec<ClusteringExamProb>,
+    ) -> Result<ExamSession> {
+        self.open_with(typ, name, spec, probs, Vec::new())
+    }
+
+    /// 登记按 `spec` 生成的新连线卷
+    pub fn open_matching(
+        &self,
+        typ: &str,
+        name: &str,
+        spec: PaperSpec,
+        grids: Vec<MatchingProb>,
+    ) -> Result<ExamSession> {
+        let probs = grids.iter().flat_map(MatchingProb::pairs).collect();
+        self.open_with(typ, name, spec, probs, grids)
+    }
+
+    fn open_with(
+        &self,
+        typ: &str,
+        name: &str,
+        spec: PaperSpec,
+        probs: Vec<ClusteringExamProb>,
+        grids: Vec<MatchingProb>,
+    ) -> Result<ExamSession> {
+        let now = now_ms();
+        let seed = spec.seed;
+        let session = ExamSession {
+            id: crate::exam::new_paper_id(),
+            typ: typ.to_string(),
+            name: name.to_string(),
+            seed,
+            gen: spec.gen,
+            mode: spec.mode,
+            policy: spec.policy,
+            direction: spec.direction,
+            sequence: spec.sequence,
+            probs,
+            grids,
+            answers: Vec::new(),
+            created_ms: now,
+            updated_ms: now,
+            issued_ms: now,
+        };
+        let _guard = self.guard();
+        self.save(&session)?;
+        log::info!(
+            "open paper {} of {}/{} with seed {}",
+            &session.id,
+            typ,
+            name,
+            seed
+        );
+        Ok(session)
+    }
+
+    /// 取出未过期的试卷以继续作答
+    pub fn resume(&self, id: &str) -> Result<ExamSession> {
+        let _guard = self.guard();
+        let mut session = self.load(id)?;
+        if session.is_expired(self.ttl) {
+            self.store.delete_paper(id)?;
+            return Err(format!("paper {} expired", id).into());
+        }
+        // 中断期间不计入每题限时，当前题目从续答时重新计时
+        if session.policy.question_limit_secs > 0 {
+            session.issued_ms = now_ms();
+            self.save(&session)?;
+        }
+        Ok(session)
+    }
+
+    /// 列出题库 `typ`/`name` 下可继续作答的试卷，最近作答的在前
+    pub fn unfinished(&self, typ: &str, name: &str) -> Result<Vec<SessionSummary>> {
+        let _guard = self.guard();
+        let mut v: Vec<ExamSession> = self
+            .store
+            .list_papers()?
+            .iter()
+            .filter_map(|id| {
+                self.load(id)
+                    .map_err(|e| log::error!("load paper {} error: {}", id, e))
+                    .ok()
+            })
+            .filter(|s| s.typ == typ && s.name == name && !s.is_expired(self.ttl))
+            .collect();
+        v.sort_by_key(|s| -s.updated_ms);
+        Ok(v.iter().map(ExamSession::summary).collect())
+    }
+
+    /// 删除过期或无法解析的试卷，返回删除数
+    pub fn expire(&self) -> Result<usize> {
+        let _guard = self.guard();
+        let mut n = 0;
+        for id in self.store.list_papers()? {
+            let expired = match self.load(&id) {
+                Ok(s) => s.is_expired(self.ttl),
+                Err(e) => {
+                    log::error!("load paper {} error: {}, dropping it", &id, e);
+                    true
+                }
+            };
+            if expired {
+                self.store.delete_paper(&id)?;
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+
+    /// 判分并记录；试卷结束时保存测试记录并移除试卷
+    pub fn answer(&self, ans: &Answer) -> std::result::Result<Graded, String> {
+        let _guard = self.guard();
+        let mut session = self
+            .load(&ans.paper_id)
+            .ok()
+            .filter(|s| !s.is_expired(self.ttl))
+            .ok_or_else(|| format!("试卷{}不存在或已结束", &ans.paper_id))?;
+        let next = session.answers.len();
+        if ans.prob != next as i32 {
+            return Err(format!("应作答第{}题，收到第{}题", next + 1, ans.prob + 1));
+        }
+        let prob = session
+            .probs
+            .get(next)
+            .ok_or_else(|| format!("试卷没有第{}题", next + 1))?;
+        let now = now_ms();
+        // 默写题按规范形式与可接受的答案比较，答对记为选中答案
+        let typed = match session.mode {
+            ExamMode::Typed => ans.typed.clone().filter(|s| !s.trim().is_empty()),
+            _ => None,
+        };
+        let picks = match session.mode {
+            ExamMode::Multi => ans.picks.clone().map(|mut v| {
+                v.sort_unstable();
+                v.dedup();
+                v
+            }),
+            _ => None,
+        };
+        if let Some(p) = picks
+            .iter()
+            .flatten()
+            .find(|p| **p < 0 || **p as usize >= prob.opts.len())
+        {
+            return Err(format!("所选选项越界：{}", p));
+        }
+        // 连线题一次提交一整组，逐对判分
+        let choices: Vec<Option<i32>> = match session.grid_at(next) {
+            Some(g) if ans.pairs.len() != g.heads.len() => {
+                return Err(format!(
+                    "第{}组连线应有{}对，收到{}对",
+                    next / g.heads.len() + 1,
+                    g.heads.len(),
+                    ans.pairs.len()
+                ));
+            }
+            Some(_) => ans.pairs.clone(),
+            None => vec![match &typed {
+                Some(s) => {
+                    let s = normalize(s);
+                    prob.opts
+                        .iter()
+                        .any(|o| normalize(&o.html) == s)
+                        .then_some(prob.answer)
+                }
+                // 默写与多选不看单选的所选项，未填或未选即为放弃
+                None if matches!(session.mode, ExamMode::Typed | ExamMode::Multi) => None,
+                None => ans.chosen,
+            }],
+        };
+        // 超过宽限的作答不论所选一律判错；客户端倒计时结束时以放弃提交，不计宽限
+        let timed_out = session.is_late(now, DEADLINE_GRACE_MS)
+            || (choices.iter().all(Option::is_none)
+                && typed.is_none()
+                && picks.is_none()
+                && session.is_late(now, 0));
+        for (i, chosen) in choices.iter().enumerate() {
+            match chosen {
+                Some(c) if *c < 0 || *c as usize >= session.probs[next + i].opts.len() => {
+                    return Err(format!("所选选项越界：{}", c));
+                }
+                None if typed.is_none()
+                    && picks.is_none()
+                    && !timed_out
+                    && !session.policy.allow_surrender =>
+                {
+                    return Err(String::from("本卷不允许放弃作答"));
+                }
+                _ => {}
+            }
+        }
+        let observed = (now - session.issued()).max(0) as u64;
+        let n = choices.len() as u64;
+        let mut pairs = Vec::new();
+        let mut credit = 0.0;
+        for (i, chosen) in choices.into_iter().enumerate() {
+            let prob = &session.probs[next + i];
+            let res = ProbResult {
+                id: prob.id,
+                head: prob.head.clone(),
+                opts: prob.opts.iter().map(|o| o.html.clone()).collect(),
+                chosen: if timed_out { None } else { chosen },
+                answer: prob.answer,
+                explain: prob.explain.clone(),
+                elapsed_ms: ans.elapsed_ms.min(observed) / n,
+                latency_ms: observed / n,
+                timed_out,
+                typed: if timed_out { None } else { typed.clone() },
+                picks: if timed_out { None } else { picks.clone() },
+                answers: prob.answers.clone(),
+            };
+            pairs.push(PairGraded {
+                correct: res.is_correct(),
+                answer: res.answer,
+            });
+            credit += res.credit() / n as f64;
+            // 只有聚类型题库有复习卷
+            if session.typ == "1" {
+                self.record_review(&session, &res, now);
+            }
+            session.answers.push(res);
+        }
+        session.updated_ms = now;
+        session.issued_ms = now;
+        let wrong = session.wrong_count();
+        let mut graded = Graded {
+            correct: pairs.iter().all(|p| p.correct),
+            answer: pairs[0].answer,
+            explain: session.probs[next].explain.clone(),
+            finished: session.is_finished(),
+            score: session.answers.len() - wrong,
+            wrong,
+            saved: None,
+            timed_out,
+            remaining_ms: session.remaining_ms(),
+            pairs: if session.grid_at(next).is_some() {
+                pairs
+            } else {
+                Vec::new()
+            },
+            answers: session.probs[next].answers.clone(),
+            credit,
+            total_credit: session.credit(),
+        };
+        if graded.finished {
+            graded.saved = Some(self.commit(&session));
+            if let Err(e) = self.store.delete_paper(&session.id) {
+                log::error!("delete finished paper {} error: {}", &session.id, e);
+            }
+        } else if let Err(e) = self.save(&session) {
+            log::error!("save paper {} error: {}", &session.id, e);
+            return Err(format!("保存作答进度出错：{}", e));
+        }
+        Ok(graded)
+    }
+
+    /// 更新题干的复习进度；失败只记录日志，不影响判分
+    fn record_review(&self, session: &ExamSession, res: &ProbResult, now: i64) {
+        let (typ, name) = (&session.typ, &session.name);
+        let result = Deck::load(&*self.store, typ, name).and_then(|mut deck| {
+            deck.record(res, now);
+            deck.save(&*self.store, typ, name)
+        });
+        if let Err(e) = result {
+            log::error!("record review of {}/{} error: {}", typ, name, e);
+        }
+    }
+
+    /// 保存结束的试卷，返回给用户的描述
+    fn commit(&self, session: &ExamSession) -> String {
+        match ron::ser::to_string(&session.result())
+            .map_err(crate::Error::from)
+            .and_then(|data| {
+                self.store
+                    .commit_history(&session.typ, &session.name, &data)
+            }) {
+            Ok(_) => String::from("保存成功"),
+            Err(e) => {
+                log::error!(
+                    "commit history of {}/{} error: {}",
+                    &session.typ,
+                    &session.name,
+                    &e
+                );
+                format!("保存历史出错：{}", e)
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::exam::{ClusteringExam, ClusteringItem, GenOptions, ProbResult};
+use crate::{Result, Store};
+use rand::seq::SliceRandom;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+const DAY_MS: i64 = 24 * 3600 * 1000;
+/// 难度系数的初值与下限（SM-2）
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// 单次复习
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Review {
+    /// 作答时间（Unix 毫秒）
+    pub at_ms: i64,
+    /// SM-2 评分，0~5，3 及以上算记住
+    pub grade: u8,
+    pub elapsed_ms: u64,
+}
+
+/// 单个题干的复习状态，按 SM-2 调度
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemState {
+    /// 连续记住的次数
+    pub reps: u32,
+    /// 难度系数
+    pub ease: f64,
+    /// 当前复习间隔（天）
+    pub interval_days: f64,
+    /// 下次复习时间（Unix 毫秒）
+    pub due_ms: i64,
+    /// 遗忘次数
+    pub lapses: u32,
+    pub reviews: Vec<Review>,
+}
+
+impl Default for ItemState {
+    fn default() -> Self {
+        Self {
+            reps: 0,
+            ease: INITIAL_EASE,
+            interval_days: 0.0,
+            due_ms: 0,
+            lapses: 0,
+            reviews: Vec::new(),
+        }
+    }
+}
+
+impl ItemState {
+    /// 记录一次评分为 `grade` 的复习并重新计算下次复习时间
+    pub fn review(&mut self, grade: u8, at_ms: i64, elapsed_ms: u64) {
+        let grade = grade.min(5);
+        if grade < 3 {
+            self.reps = 0;
+            self.lapses += 1;
+            self.interval_days = 1.0;
+        } else {
+            self.interval_days = match self.reps {
+                0 => 1.0,
+                1 => 6.0,
+                _ => (self.interval_days * self.ease).round(),
+            };
+            self.reps += 1;
+        }
+        let q = (5 - grade) as f64;
+        self.ease = (self.ease + 0.1 - q * (0.08 + q * 0.02)).max(MIN_EASE);
+        self.due_ms = at_ms + (self.interval_days * DAY_MS as f64) as i64;
+        self.reviews.push(Review {
+            at_ms,
+            grade,
+            elapsed_ms,
+        });
+    }
+
+    pub fn is_due(&self, now_ms: i64) -> bool {
+        self.due_ms <= now_ms
+    }
+}
+
+/// 由判分结果得出 SM-2 评分：放弃 0，答错 1，答对按用时 3~5
+pub fn grade(res: &ProbResult) -> u8 {
+    match (res.is_surrendered(), res.is_correct()) {
+        (true, _) => 0,
+        (_, false) => 1,
+        _ if res.elapsed_ms <= 5_000 => 5,
+        _ if res.elapsed_ms <= 15_000 => 4,
+        _ => 3,
+    }
+}
+
+/// 一个题库的复习进度，以题干的表示为键
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Deck {
+    pub items: BTreeMap<String, ItemState>,
+}
+
+impl Deck {
+    pub fn load(store: &dyn Store, typ: &str, name: &str) -> Result<Self> {
+        Ok(match store.load_srs(typ, name)? {
+            Some(s) => ron::de::from_str(&s)?,
+            None => Default::default(),
+        })
+    }
+
+    pub fn save(&self, store: &dyn Store, typ: &str, name: &str) -> Result<()> {
+        store.save_srs(typ, name, &ron::ser::to_string(self)?)
+    }
+
+    /// 按判分结果更新题干的复习状态
+    pub fn record(&mut self, res: &ProbResult, at_ms: i64) {
+        self.items
+            .entry(res.head.clone())
+            .or_default()
+            .review(grade(res), at_ms, res.elapsed_ms);
+    }
+
+    /// 题库中已到期的题干数
+    pub fn due_count(&self, bank: &ClusteringExam, now_ms: i64) -> usize {
+        distinct(bank)
+            .iter()
+            .filter(|i| self.items.get(&i.data).is_some_and(|s| s.is_due(now_ms)))
+            .count()
+    }
+
+    /// 挑选复习卷的题干：先是到期最久的，再是未学过的，最后是最早到期的；不重复，
+    /// 只取 `opts` 允许作题干的项
+    pub fn pick<R: ?Sized + rand::Rng>(
+        &self,
+        bank: &ClusteringExam,
+        opts: &GenOptions,
+        count: usize,
+        now_ms: i64,
+        rng: &mut R,
+    ) -> Vec<ClusteringItem> {
+        let (mut seen, mut fresh): (Vec<ClusteringItem>, Vec<ClusteringItem>) = distinct(bank)
+            .into_iter()
+            .filter(|i| opts.is_head(i))
+            .partition(|i| self.items.contains_key(&i.data));
+        seen.sort_by_key(|i| self.items[&i.data].due_ms);
+        fresh.shuffle(rng);
+        let split = seen.partition_point(|i| self.items[&i.data].is_due(now_ms));
+        let later = seen.split_off(split);
+        seen.into_iter()
+            .chain(fresh)
+            .chain(later)
+            .take(count)
+            .collect()
+    }
+}
+
+/// 题库中表示不重复的各项，保持原顺序
+fn distinct(bank: &ClusteringExam) -> Vec<ClusteringItem> {
+    let mut set = HashSet::new();
+    bank.data
+        .iter()
+        .filter(|i| set.insert(i.data.as_str()))
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,260 @@
+use crate::exam::{ClusteringExam, ClusteringItem, ExamMode, ExamResult, Weights};
+use crate::{Result, Store};
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// 读取题库的全部结构化测试记录及其标签，按时间升序；早期的 HTML 记录跳过
+pub fn load_tagged_results(
+    store: &dyn Store,
+    typ: &str,
+    name: &str,
+) -> Result<Vec<(String, ExamResult)>> {
+    let mut v = Vec::new();
+    for tag in store.list_history(typ, name)? {
+        let data = store.load_history(typ, name, &tag)?;
+        if let Ok(result) = ron::de::from_str::<ExamResult>(&data) {
+            v.push((tag, result));
+        }
+    }
+    Ok(v)
+}
+
+/// 读取题库的全部结构化测试记录，按时间升序
+pub fn load_results(store: &dyn Store, typ: &str, name: &str) -> Result<Vec<ExamResult>> {
+    Ok(load_tagged_results(store, typ, name)?
+        .into_iter()
+        .map(|(_, r)| r)
+        .collect())
+}
+
+/// 过往的错误统计：各题干的作答与答错次数，以及聚类间的混淆次数
+#[derive(Debug, Default)]
+pub struct Mistakes {
+    /// 题干 -> (作答次数, 答错次数)
+    items: HashMap<String, (u32, u32)>,
+    /// (题干聚类, 误选的聚类) -> 次数
+    pairs: HashMap<(i32, i32), u32>,
+}
+
+impl Mistakes {
+    /// 以题库 `bank` 的当前聚类统计 `results`；已不在题库中的表示忽略
+    pub fn from_results(bank: &ClusteringExam, results: &[ExamResult]) -> Self {
+        let clsid: HashMap<&str, i32> = bank
+            .data
+            .iter()
+            .map(|i| (i.data.as_str(), i.clsid))
+            .collect();
+        let mut m = Self::default();
+        for p in results.iter().flat_map(|r| r.probs.iter()) {
+            let e = m.items.entry(p.head.clone()).or_default();
+            e.0 += 1;
+            if p.is_correct() {
+                continue;
+            }
+            e.1 += 1;
+            let chosen = p
+                .chosen
+                .and_then(|c| p.opts.get(c as usize))
+                .and_then(|o| clsid.get(o.as_str()));
+            if let (Some(h), Some(c)) = (clsid.get(p.head.as_str()), chosen) {
+                *m.pairs.entry((*h, *c)).or_default() += 1;
+            }
+        }
+        m
+    }
+
+    /// 题干的错误率，加一平滑，未作答过的为 0.5
+    pub fn error_rate(&self, head: &str) -> f64 {
+        let (n, w) = self.items.get(head).cloned().unwrap_or_default();
+        (w as f64 + 1.0) / (n as f64 + 2.0)
+    }
+
+    /// 题干聚类 `head` 误选为聚类 `chosen` 的次数
+    pub fn confusions(&self, head: i32, chosen: i32) -> u32 {
+        self.pairs.get(&(head, chosen)).cloned().unwrap_or_default()
+    }
+}
+
+impl Weights for Mistakes {
+    fn head(&self, item: &ClusteringItem) -> f64 {
+        self.error_rate(&item.data)
+    }
+
+    fn distractor(&self, head: &ClusteringItem, item: &ClusteringItem) -> f64 {
+        1.0 + self.confusions(head.clsid, item.clsid) as f64
+    }
+}
+
+/// 聚类的标签，即其成员的表示
+#[derive(Serialize, Debug)]
+pub struct ClusterLabel {
+    pub clsid: i32,
+    pub label: String,
+}
+
+/// 混淆矩阵的一行：题干属于 `clsid` 时所选选项的聚类分布
+#[derive(Serialize, Debug)]
+pub struct MatrixRow {
+    pub clsid: i32,
+    /// 与 `BankStats::clusters` 对应的次数，对角线为答对
+    pub cells: Vec<u32>,
+    /// 放弃次数
+    pub surrendered: u32,
+    /// 本行的作答次数
+    pub total: u32,
+}
+
+/// 单个题干的统计
+#[derive(Serialize, Debug)]
+pub struct ItemStats {
+    pub head: String,
+    pub clsid: i32,
+    pub attempts: u32,
+    pub correct: u32,
+    /// 正确率，0~1
+    pub accuracy: f64,
+    /// 平均作答用时（毫秒）
+    pub mean_ms: u64,
+}
+
+/// 一个题库的作答统计
+#[derive(Serialize, Debug)]
+pub struct BankStats {
+    /// 参与统计的测试记录数
+    pub results: usize,
+    pub clusters: Vec<ClusterLabel>,
+    pub matrix: Vec<MatrixRow>,
+    /// 按正确率升序
+    pub items: Vec<ItemStats>,
+}
+
+impl BankStats {
+    /// 以题库 `bank` 的当前聚类统计 `results`；已不在题库中的表示忽略
+    pub fn new(bank: &ClusteringExam, results: &[ExamResult]) -> Self {
+        let clsid: HashMap<&str, i32> = bank
+            .data
+            .iter()
+            .map(|i| (i.data.as_str(), i.clsid))
+            .collect();
+        let table = bank.table();
+        let index: HashMap<i32, usize> = table
+            .iter()
+            .enumerate()
+            .map(|(n, c)| (c[0].clsid, n))
+            .collect();
+        let mut matrix: Vec<MatrixRow> = table
+            .iter()
+            .map(|c| MatrixRow {
+                clsid: c[0].clsid,
+                cells: vec![0; table.len()],
+                surrendered: 0,
+                total: 0,
+            })
+            .collect();
+        // 题干 -> (作答次数, 答对次数, 总用时)
+        let mut items: BTreeMap<&str, (u32, u32, u64)> = BTreeMap::new();
+        for p in results.iter().flat_map(|r| r.probs.iter()) {
+            let Some(h) = clsid.get(p.head.as_str()) else {
+                continue;
+            };
+            let e = items.entry(p.head.as_str()).or_default();
+            e.0 += 1;
+            e.1 += p.is_correct() as u32;
+            e.2 += p.elapsed_ms;
+            let row = &mut matrix[index[h]];
+            row.total += 1;
+            match p.chosen.and_then(|c| p.opts.get(c as usize)) {
+                None if p.is_surrendered() => row.surrendered += 1,
+                // 默写答错，没有所选的聚类
+                None => {}
+                Some(o) => {
+                    if let Some(c) = clsid.get(o.as_str()) {
+                        row.cells[index[c]] += 1;
+                    }
+                }
+            }
+        }
+        let mut items: Vec<ItemStats> = items
+            .into_iter()
+            .map(|(head, (attempts, correct, ms))| ItemStats {
+                head: head.to_string(),
+                clsid: clsid[head],
+                attempts,
+                correct,
+                accuracy: correct as f64 / attempts as f64,
+                mean_ms: ms / attempts as u64,
+            })
+            .collect();
+        items.sort_by(|a, b| a.accuracy.total_cmp(&b.accuracy));
+        Self {
+            results: results.len(),
+            clusters: table
+                .iter()
+                .map(|c| ClusterLabel {
+                    clsid: c[0].clsid,
+                    label: c
+                        .iter()
+                        .map(|i| i.data.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(" "),
+                })
+                .collect(),
+            matrix,
+            items,
+        }
+    }
+}
+
+/// 成绩走势中的一次测试
+#[derive(Serialize, Debug)]
+pub struct TrendPoint {
+    pub tag: String,
+    pub mode: ExamMode,
+    pub score: usize,
+    /// 作答的题数，提前结束时少于 `total`
+    pub answered: usize,
+    /// 试卷题量，旧记录取 `answered`
+    pub total: usize,
+    pub early_stop: bool,
+    /// 得分占题量的百分比
+    pub score_pct: f64,
+    /// 作答题数占题量的百分比
+    pub answered_pct: f64,
+    pub time_taken_ms: u64,
+    /// 平均每题用时（毫秒）
+    pub mean_ms: u64,
+}
+
+/// 按时间顺序列出各次测试的成绩
+pub fn trend(results: &[(String, ExamResult)]) -> Vec<TrendPoint> {
+    results
+        .iter()
+        .map(|(tag, r)| {
+            let answered = r.probs.len();
+            let total = if r.prob_count > 0 {
+                r.prob_count
+            } else {
+                answered
+            };
+            let pct = |n: usize| match total {
+                0 => 0.0,
+                _ => n as f64 * 100.0 / total as f64,
+            };
+            TrendPoint {
+                tag: tag.clone(),
+                mode: r.mode,
+                score: r.score,
+                answered,
+                total,
+                early_stop: r.early_stop.is_some(),
+                score_pct: pct(r.score),
+                answered_pct: pct(answered),
+                time_taken_ms: r.time_taken_ms,
+                mean_ms: match answered {
+                    0 => 0,
+                    n => r.probs.iter().map(|p| p.elapsed_ms).sum::<u64>() / n as u64,
+                },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,75 @@
+use crate::Result;
+use std::sync::Arc;
+
+mod fs;
+mod mem;
+mod sqlite;
+
+pub use self::fs::FsStore;
+pub use self::mem::MemStore;
+pub use self::sqlite::{ImportReport, SqliteStore};
+
+/// 题库、历史记录、作答中试卷与复习进度的持久化后端
+///
+/// 题库以 RON 文本存取，类型化的读写见 `load_test_data` 与 `commit_test_data`。
+pub trait Store: Send + Sync {
+    /// 读取题库 `typ`/`name` 的 RON 文本
+    fn load_bank(&self, typ: &str, name: &str) -> Result<String>;
+    /// 写入（覆盖）题库
+    fn save_bank(&self, typ: &str, name: &str, data: &str) -> Result<()>;
+    fn delete_bank(&self, typ: &str, name: &str) -> Result<()>;
+    /// 列出某测试类型下的所有题库名
+    fn list_banks(&self, typ: &str) -> Result<Vec<String>>;
+
+    /// 保存一次测试记录（RON 格式的 `ExamResult`），返回其标签
+    ///
+    /// 早期版本保存的是作答页面的 HTML，读取时由调用方区分。
+    fn commit_history(&self, typ: &str, name: &str, data: &str) -> Result<String>;
+    fn load_history(&self, typ: &str, name: &str, tag: &str) -> Result<String>;
+    fn delete_history(&self, typ: &str, name: &str, tag: &str) -> Result<()>;
+    /// 列出题库的所有测试记录标签，按时间升序
+    fn list_history(&self, typ: &str, name: &str) -> Result<Vec<String>>;
+
+    /// 保存（覆盖）作答中的试卷，`data` 为 RON 格式的 `ExamSession`
+    fn save_paper(&self, id: &str, data: &str) -> Result<()>;
+    fn load_paper(&self, id: &str) -> Result<String>;
+    fn delete_paper(&self, id: &str) -> Result<()>;
+    /// 列出所有作答中试卷的 id
+    fn list_papers(&self) -> Result<Vec<String>>;
+
+    /// 读取题库的复习进度（RON 格式的 `Deck`），尚无记录时为 `None`
+    fn load_srs(&self, typ: &str, name: &str) -> Result<Option<String>>;
+    /// 保存（覆盖）题库的复习进度
+    fn save_srs(&self, typ: &str, name: &str, data: &str) -> Result<()>;
+}
+
+/// 在 warp filter 间共享的存储句柄
+#[derive(Clone)]
+pub struct SharedStore(Arc<dyn Store>);
+
+impl SharedStore {
+    pub fn new<S: Store + 'static>(store: S) -> Self {
+        Self(Arc::new(store))
+    }
+}
+
+impl std::ops::Deref for SharedStore {
+    type Target = dyn Store;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// 新测试记录的标签，即本地时间
+fn history_tag() -> String {
+    chrono::Local::now().format("%F_%Hh%Mm%Ss").to_string()
+}
+
+/// 拒绝可能逃出数据目录的名称
+pub(crate) fn check_name(kind: &str, name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(format!("invalid {} name {:?}", kind, name).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use super::{check_name, history_tag, Store};
+use crate::{Config, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// 目录存储：`data/test/<typ>/<name>.ron`、`data/history/<typ>/<name>/<tag>.ron`
+/// 、`data/session/<id>.ron` 与 `data/srs/<typ>/<name>.ron`
+///
+/// 早期版本的测试记录是 `<tag>.html`，仍可列出与读取。
+pub struct FsStore {
+    test_dir: PathBuf,
+    history_root: PathBuf,
+    session_dir: PathBuf,
+    srs_dir: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(cfg: &Config) -> Self {
+        Self {
+            test_dir: cfg.test_dir(),
+            history_root: cfg.history_root(),
+            session_dir: cfg.session_dir(),
+            srs_dir: cfg.srs_dir(),
+        }
+    }
+
+    fn bank_path(&self, typ: &str, name: &str) -> Result<PathBuf> {
+        check_name("test type", typ)?;
+        check_name("test", name)?;
+        Ok(self.test_dir.join(typ).join(format!("{}.ron", name)))
+    }
+
+    fn history_dir(&self, typ: &str, name: &str) -> Result<PathBuf> {
+        check_name("test type", typ)?;
+        check_name("test", name)?;
+        let p = self.history_root.join(typ).join(name);
+        ensure_dir(&p)?;
+        Ok(p)
+    }
+
+    fn paper_path(&self, id: &str) -> Result<PathBuf> {
+        check_name("paper", id)?;
+        Ok(self.session_dir.join(format!("{}.ron", id)))
+    }
+
+    fn srs_path(&self, typ: &str, name: &str) -> Result<PathBuf> {
+        check_name("test type", typ)?;
+        check_name("test", name)?;
+        Ok(self.srs_dir.join(typ).join(format!("{}.ron", name)))
+    }
+
+    /// 已有复习进度的 `(typ, name)`
+    pub fn srs_kinds(&self) -> Result<Vec<(String, String)>> {
+        let mut v = Vec::new();
+        for typ in list_dirs(&self.srs_dir)? {
+            for name in list_stems(&self.srs_dir.join(&typ), ".ron")? {
+                v.push((typ.clone(), name));
+            }
+        }
+        Ok(v)
+    }
+
+    /// 已有题库的测试类型
+    pub fn bank_types(&self) -> Result<Vec<String>> {
+        list_dirs(&self.test_dir)
+    }
+
+    /// 已有测试记录的 `(typ, name)`
+    pub fn history_kinds(&self) -> Result<Vec<(String, String)>> {
+        let mut v = Vec::new();
+        for typ in list_dirs(&self.history_root)? {
+            for name in list_dirs(&self.history_root.join(&typ))? {
+                v.push((typ.clone(), name));
+            }
+        }
+        Ok(v)
+    }
+
+    fn history_path(&self, typ: &str, name: &str, tag: &str) -> Result<PathBuf> {
+        check_name("history", tag)?;
+        Ok(self.history_dir(typ, name)?.join(format!("{}.ron", tag)))
+    }
+
+    /// 已存在的测试记录文件，兼容早期的 `.html`
+    fn existing_history_path(&self, typ: &str, name: &str, tag: &str) -> Result<PathBuf> {
+        let p = self.history_path(typ, name, tag)?;
+        if p.exists() {
+            return Ok(p);
+        }
+        Ok(p.with_extension("html"))
+    }
+}
+
+fn ensure_dir(p: &Path) -> Result<()> {
+    if !p.exists() {
+        std::fs::create_dir_all(p).map_err(|e| format!("create dir {:?} error: {}", p, e))?;
+    }
+    Ok(())
+}
+
+fn read_to_string(p: &Path) -> Result<String> {
+    log::info!("opening {:?}", p);
+    let mut s = String::new();
+    File::open(p)?.read_to_string(&mut s)?;
+    Ok(s)
+}
+
+/// 列出目录 `p` 下的子目录名，`p` 不存在时为空
+fn list_dirs(p: &Path) -> Result<Vec<String>> {
+    if !p.exists() {
+        return Ok(Vec::new());
+    }
+    let mut v = Vec::new();
+    for entry in p.read_dir()? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        match entry.file_name().into_string() {
+            Ok(s) => v.push(s),
+            Err(e) => log::error!("dir name {:?} in {:?} cannot convert to utf8", e, p),
+        }
+    }
+    Ok(v)
+}
+
+/// 列出目录 `p` 下以 `suffix` 结尾的文件名（去掉后缀）
+fn list_stems(p: &Path, suffix: &str) -> Result<Vec<String>> {
+    let d = p
+        .read_dir()
+        .map_err(|e| format!("open dir {:?} error: {}", p, e))?;
+    Ok(d.filter_map(|result| match result {
+        Ok(entry) => entry
+            .file_name()
+            .into_string()
+            .map_err(|e| log::error!("file name {:?} in {:?} cannot convert to utf8", e, p))
+            .ok()
+            .and_then(|s| s.strip_suffix(suffix).map(String::from)),
+        Err(e) => {
+            log::error!("read dir {:?} error: {}", p, e);
+            None
+        }
+    })
+    .collect())
+}
+
+impl Store for FsStore {
+    fn load_bank(&self, typ: &str, name: &str) -> Result<String> {
+        read_to_string(&self.bank_path(typ, name)?)
+    }
+
+    fn save_bank(&self, typ: &str, name: &str, data: &str) -> Result<()> {
+        let p = self.bank_path(typ, name)?;
+        if let Some(d) = p.parent() {
+            ensure_dir(d)?;
+        }
+        let mut out = File::create(p)?;
+        out.write_all(data.as_bytes())?;
+        Ok(())
+    }
+
+    fn delete_bank(&self, typ: &str, name: &str) -> Result<()> {
+        std::fs::remove_file(self.bank_path(typ, name)?)?;
+        Ok(())
+    }
+
+    fn list_banks(&self, typ: &str) -> Result<Vec<String>> {
+        check_name("test type", typ)?;
+        let p = self.test_dir.join(typ);
+        ensure_dir(&p)?;
+        list_stems(&p, ".ron")
+    }
+
+    fn commit_history(&self, typ: &str, name: &str, data: &str) -> Result<String> {
+        let base = history_tag();
+        let mut tag = base.clone();
+        let mut n = 1;
+        // 同一秒内结束的测试加后缀，不覆盖已有记录
+        let mut out = loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.history_path(typ, name, &tag)?)
+            {
+                Ok(f) => break f,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    tag = format!("{}_{}", base, n);
+                    n += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+        log::info!("commiting new history {}/{}/{}", typ, name, &tag);
+        write!(&mut out, "{}", data)?;
+        Ok(tag)
+    }
+
+    fn load_history(&self, typ: &str, name: &str, tag: &str) -> Result<String> {
+        read_to_string(&self.existing_history_path(typ, name, tag)?)
+    }
+
+    fn delete_history(&self, typ: &str, name: &str, tag: &str) -> Result<()> {
+        std::fs::remove_file(self.existing_history_path(typ, name, tag)?)?;
+        Ok(())
+    }
+
+    fn list_history(&self, typ: &str, name: &str) -> Result<Vec<String>> {
+        let d = self.history_dir(typ, name)?;
+        let mut v = list_stems(&d, ".ron")?;
+        v.extend(list_stems(&d, ".html")?);
+        v.sort();
+        v.dedup();
+        Ok(v)
+    }
+
+    fn save_paper(&self, id: &str, data: &str) -> Result<()> {
+        let p = self.paper_path(id)?;
+        ensure_dir(&self.session_dir)?;
+        let mut out = File::create(p)?;
+        out.write_all(data.as_bytes())?;
+        Ok(())
+    }
+
+    fn load_paper(&self, id: &str) -> Result<String> {
+        read_to_string(&self.paper_path(id)?)
+    }
+
+    fn delete_paper(&self, id: &str) -> Result<()> {
+        std::fs::remove_file(self.paper_path(id)?)?;
+        Ok(())
+    }
+
+    fn list_papers(&self) -> Result<Vec<String>> {
+        ensure_dir(&self.session_dir)?;
+        list_stems(&self.session_dir, ".ron")
+    }
+
+    fn load_srs(&self, typ: &str, name: &str) -> Result<Option<String>> {
+        let p = self.srs_path(typ, name)?;
+        if !p.exists() {
+            return Ok(None);
+        }
+        read_to_string(&p).map(Some)
+    }
+
+    fn save_srs(&self, typ: &str, name: &str, data: &str) -> Result<()> {
+        let p = self.srs_path(typ, name)?;
+        if let Some(d) = p.parent() {
+            ensure_dir(d)?;
+        }
+        let mut out = File::create(p)?;
+        out.write_all(data.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use super::{history_tag, Store};
+use crate::Result;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+type Key = (String, String);
+
+/// 内存存储，进程退出即丢失；用于测试或临时实例
+#[derive(Default)]
+pub struct MemStore {
+    banks: Mutex<BTreeMap<Key, String>>,
+    history: Mutex<BTreeMap<Key, BTreeMap<String, String>>>,
+    papers: Mutex<BTreeMap<String, String>>,
+    srs: Mutex<BTreeMap<Key, String>>,
+}
+
+impl MemStore {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+fn key(typ: &str, name: &str) -> Key {
+    (typ.to_string(), name.to_string())
+}
+
+fn not_found(what: &str, typ: &str, name: &str) -> crate::Error {
+    format!("{} {}/{} not found", what, typ, name).into()
+}
+
+impl Store for MemStore {
+    fn load_bank(&self, typ: &str, name: &str) -> Result<String> {
+        self.banks
+            .lock()
+            .unwrap()
+            .get(&key(typ, name))
+            .cloned()
+            .ok_or_else(|| not_found("test", typ, name))
+    }
+
+    fn save_bank(&self, typ: &str, name: &str, data: &str) -> Result<()> {
+        self.banks
+            .lock()
+            .unwrap()
+            .insert(key(typ, name), data.to_string());
+        Ok(())
+    }
+
+    fn delete_bank(&self, typ: &str, name: &str) -> Result<()> {
+        self.banks
+            .lock()
+            .unwrap()
+            .remove(&key(typ, name))
+            .map(|_| ())
+            .ok_or_else(|| not_found("test", typ, name))
+    }
+
+    fn list_banks(&self, typ: &str) -> Result<Vec<String>> {
+        Ok(self
+            .banks
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|(t, _)| t == typ)
+            .map(|(_, n)| n.clone())
+            .collect())
+    }
+
+    fn commit_history(&self, typ: &str, name: &str, data: &str) -> Result<String> {
+        let mut history = self.history.lock().unwrap();
+        let records = history.entry(key(typ, name)).or_default();
+        let base = history_tag();
+        let mut tag = base.clone();
+        let mut n = 1;
+        while records.contains_key(&tag) {
+            tag = format!("{}_{}", base, n);
+            n += 1;
+        }
+        records.insert(tag.clone(), data.to_string());
+        Ok(tag)
+    }
+
+    fn load_history(&self, typ: &str, name: &str, tag: &str) -> Result<String> {
+        self.history
+            .lock()
+            .unwrap()
+            .get(&key(typ, name))
+            .and_then(|m| m.get(tag))
+            .cloned()
+            .ok_or_else(|| not_found("history", typ, name))
+    }
+
+    fn delete_history(&self, typ: &str, name: &str, tag: &str) -> Result<()> {
+        self.history
+            .lock()
+            .unwrap()
+            .get_mut(&key(typ, name))
+            .and_then(|m| m.remove(tag))
+            .map(|_| ())
+            .ok_or_else(|| not_found("history", typ, name))
+    }
+
+    fn list_history(&self, typ: &str, name: &str) -> Result<Vec<String>> {
+        Ok(self
+            .history
+            .lock()
+            .unwrap()
+            .get(&key(typ, name))
+            .map(|m| m.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn save_paper(&self, id: &str, data: &str) -> Result<()> {
+        self.papers
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), data.to_string());
+        Ok(())
+    }
+
+    fn load_paper(&self, id: &str) -> Result<String> {
+        self.papers
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("paper {} not found", id).into())
+    }
+
+    fn delete_paper(&self, id: &str) -> Result<()> {
+        self.papers
+            .lock()
+            .unwrap()
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| format!("paper {} not found", id).into())
+    }
+
+    fn list_papers(&self) -> Result<Vec<String>> {
+        Ok(self.papers.lock().unwrap().keys().cloned().collect())
+    }
+
+    fn load_srs(&self, typ: &str, name: &str) -> Result<Option<String>> {
+        Ok(self.srs.lock().unwrap().get(&key(typ, name)).cloned())
+    }
+
+    fn save_srs(&self, typ: &str, name: &str, data: &str) -> Result<()> {
+        self.srs
+            .lock()
+            .unwrap()
+            .insert(key(typ, name), data.to_string());
+        Ok(())
+    }
+}
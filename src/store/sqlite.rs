@@ -0,0 +1,335 @@
+use super::{history_tag, FsStore, Store};
+use crate::exam::ExamResult;
+use crate::{Config, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::Mutex;
+
+/// 依次执行的 schema 迁移，`PRAGMA user_version` 记录已执行的条数
+const MIGRATIONS: &[&str] = &[
+    // 1: 题库与测试记录
+    "CREATE TABLE bank (
+        typ TEXT NOT NULL,
+        name TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (typ, name)
+    );
+    CREATE TABLE history (
+        id INTEGER PRIMARY KEY,
+        typ TEXT NOT NULL,
+        name TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        data TEXT NOT NULL,
+        UNIQUE (typ, name, tag)
+    );",
+    // 2: 结构化测试记录的逐题结果
+    "CREATE TABLE result_item (
+        history_id INTEGER NOT NULL REFERENCES history (id) ON DELETE CASCADE,
+        seq INTEGER NOT NULL,
+        head TEXT NOT NULL,
+        chosen INTEGER,
+        answer INTEGER NOT NULL,
+        correct INTEGER NOT NULL,
+        elapsed_ms INTEGER NOT NULL,
+        PRIMARY KEY (history_id, seq)
+    );",
+    // 3: 作答中的试卷
+    "CREATE TABLE paper (
+        id TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );",
+    // 4: 题库的复习进度
+    "CREATE TABLE srs (
+        typ TEXT NOT NULL,
+        name TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (typ, name)
+    );",
+    // 5: 逐题结果的服务端延迟与超时标记
+    "ALTER TABLE result_item ADD COLUMN latency_ms INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE result_item ADD COLUMN timed_out INTEGER NOT NULL DEFAULT 0;",
+    // 6: 默写题的输入
+    "ALTER TABLE result_item ADD COLUMN typed TEXT;",
+];
+
+/// 内嵌 SQLite 存储，数据库文件位于 `data/elearn.db`
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+/// `import_fs` 的导入统计
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub banks: usize,
+    pub history: usize,
+    pub srs: usize,
+    /// 数据库中已存在而跳过的条目
+    pub skipped: usize,
+}
+
+impl SqliteStore {
+    pub fn open(cfg: &Config) -> Result<Self> {
+        let p = cfg.db_path();
+        if let Some(d) = p.parent() {
+            std::fs::create_dir_all(d)?;
+        }
+        log::info!("opening sqlite store {:?}", &p);
+        Self::with_connection(Connection::open(p)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// 导入目录存储中的全部题库、测试记录与复习进度，已存在的条目保持不变
+    pub fn import_fs(&self, fs: &FsStore) -> Result<ImportReport> {
+        let mut report = ImportReport::default();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for typ in fs.bank_types()? {
+            for name in fs.list_banks(&typ)? {
+                let data = fs.load_bank(&typ, &name)?;
+                match tx.execute(
+                    "INSERT OR IGNORE INTO bank (typ, name, data) VALUES (?1, ?2, ?3)",
+                    params![typ, name, data],
+                )? {
+                    0 => report.skipped += 1,
+                    _ => report.banks += 1,
+                }
+            }
+        }
+        for (typ, name) in fs.history_kinds()? {
+            for tag in fs.list_history(&typ, &name)? {
+                let data = fs.load_history(&typ, &name, &tag)?;
+                match insert_history(&tx, &typ, &name, &tag, &data)? {
+                    false => report.skipped += 1,
+                    true => report.history += 1,
+                }
+            }
+        }
+        for (typ, name) in fs.srs_kinds()? {
+            if let Some(data) = fs.load_srs(&typ, &name)? {
+                match tx.execute(
+                    "INSERT OR IGNORE INTO srs (typ, name, data) VALUES (?1, ?2, ?3)",
+                    params![typ, name, data],
+                )? {
+                    0 => report.skipped += 1,
+                    _ => report.srs += 1,
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        log::info!("sqlite store migrating to version {}", i + 1);
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// 插入一条测试记录，结构化结果同时展开到 `result_item`；已存在时返回 `false`
+fn insert_history(conn: &Connection, typ: &str, name: &str, tag: &str, data: &str) -> Result<bool> {
+    if conn.execute(
+        "INSERT OR IGNORE INTO history (typ, name, tag, data) VALUES (?1, ?2, ?3, ?4)",
+        params![typ, name, tag, data],
+    )? == 0
+    {
+        return Ok(false);
+    }
+    let id = conn.last_insert_rowid();
+    if let Ok(result) = ron::de::from_str::<ExamResult>(data) {
+        let mut stmt = conn.prepare(
+            "INSERT INTO result_item
+             (history_id, seq, head, chosen, answer, correct, elapsed_ms, latency_ms, timed_out, typed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )?;
+        for p in result.probs.iter() {
+            stmt.execute(params![
+                id,
+                p.id,
+                p.head,
+                p.chosen,
+                p.answer,
+                p.is_correct(),
+                p.elapsed_ms as i64,
+                p.latency_ms as i64,
+                p.timed_out,
+                p.typed
+            ])?;
+        }
+    }
+    Ok(true)
+}
+
+fn not_found(what: &str, typ: &str, name: &str) -> crate::Error {
+    format!("{} {}/{} not found", what, typ, name).into()
+}
+
+impl Store for SqliteStore {
+    fn load_bank(&self, typ: &str, name: &str) -> Result<String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT data FROM bank WHERE typ = ?1 AND name = ?2",
+                params![typ, name],
+                |r| r.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| not_found("test", typ, name))
+    }
+
+    fn save_bank(&self, typ: &str, name: &str, data: &str) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO bank (typ, name, data) VALUES (?1, ?2, ?3)
+             ON CONFLICT (typ, name) DO UPDATE SET data = excluded.data",
+            params![typ, name, data],
+        )?;
+        Ok(())
+    }
+
+    fn delete_bank(&self, typ: &str, name: &str) -> Result<()> {
+        match self.conn.lock().unwrap().execute(
+            "DELETE FROM bank WHERE typ = ?1 AND name = ?2",
+            params![typ, name],
+        )? {
+            0 => Err(not_found("test", typ, name)),
+            _ => Ok(()),
+        }
+    }
+
+    fn list_banks(&self, typ: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT name FROM bank WHERE typ = ?1 ORDER BY name")?;
+        let v = stmt
+            .query_map(params![typ], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(v)
+    }
+
+    fn commit_history(&self, typ: &str, name: &str, data: &str) -> Result<String> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let base = history_tag();
+        let mut tag = base.clone();
+        let mut n = 1;
+        while !insert_history(&tx, typ, name, &tag, data)? {
+            tag = format!("{}_{}", base, n);
+            n += 1;
+        }
+        tx.commit()?;
+        log::info!("commiting new history {}/{}/{}", typ, name, &tag);
+        Ok(tag)
+    }
+
+    fn load_history(&self, typ: &str, name: &str, tag: &str) -> Result<String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT data FROM history WHERE typ = ?1 AND name = ?2 AND tag = ?3",
+                params![typ, name, tag],
+                |r| r.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| not_found("history", typ, name))
+    }
+
+    fn delete_history(&self, typ: &str, name: &str, tag: &str) -> Result<()> {
+        match self.conn.lock().unwrap().execute(
+            "DELETE FROM history WHERE typ = ?1 AND name = ?2 AND tag = ?3",
+            params![typ, name, tag],
+        )? {
+            0 => Err(not_found("history", typ, name)),
+            _ => Ok(()),
+        }
+    }
+
+    fn list_history(&self, typ: &str, name: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT tag FROM history WHERE typ = ?1 AND name = ?2 ORDER BY tag")?;
+        let v = stmt
+            .query_map(params![typ, name], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(v)
+    }
+
+    fn save_paper(&self, id: &str, data: &str) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO paper (id, data) VALUES (?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET data = excluded.data",
+            params![id, data],
+        )?;
+        Ok(())
+    }
+
+    fn load_paper(&self, id: &str) -> Result<String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT data FROM paper WHERE id = ?1", params![id], |r| {
+                r.get(0)
+            })
+            .optional()?
+            .ok_or_else(|| format!("paper {} not found", id).into())
+    }
+
+    fn delete_paper(&self, id: &str) -> Result<()> {
+        match self
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM paper WHERE id = ?1", params![id])?
+        {
+            0 => Err(format!("paper {} not found", id).into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn list_papers(&self) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id FROM paper")?;
+        let v = stmt
+            .query_map([], |r| r.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(v)
+    }
+
+    fn load_srs(&self, typ: &str, name: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT data FROM srs WHERE typ = ?1 AND name = ?2",
+                params![typ, name],
+                |r| r.get(0),
+            )
+            .optional()?)
+    }
+
+    fn save_srs(&self, typ: &str, name: &str, data: &str) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO srs (typ, name, data) VALUES (?1, ?2, ?3)
+             ON CONFLICT (typ, name) DO UPDATE SET data = excluded.data",
+            params![typ, name, data],
+        )?;
+        Ok(())
+    }
+}
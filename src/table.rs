@@ -0,0 +1,141 @@
+use crate::exam::ClusteringItem;
+use serde_derive::{Deserialize, Serialize};
+
+/// 表格中每行的含义
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// 每行一个聚类，各列为其成员
+    Rows,
+    /// 每行一项：`表示,聚类标识[,标签]`
+    Pairs,
+}
+
+/// 解析出错的行
+#[derive(Serialize, Debug, Clone)]
+pub struct LineError {
+    /// 行号，从 1 开始
+    pub line: usize,
+    pub message: String,
+}
+
+/// 含制表符的文本按 TSV 解析，否则按 CSV
+fn delimiter(text: &str) -> char {
+    if text.contains('\t') {
+        '\t'
+    } else {
+        ','
+    }
+}
+
+/// 以 `delim` 切分一行；字段可用双引号包裹，其中的 `""` 表示一个引号
+fn split_record(line: &str, delim: char) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(String::from("引号未闭合")),
+                }
+            }
+            match chars.next() {
+                None => {
+                    fields.push(field);
+                    return Ok(fields);
+                }
+                Some(c) if c == delim => {}
+                Some(c) => return Err(format!("引号后应为分隔符，实际为“{}”", c)),
+            }
+        } else {
+            loop {
+                match chars.next() {
+                    None => {
+                        fields.push(field);
+                        return Ok(fields);
+                    }
+                    Some(c) if c == delim => break,
+                    Some(c) => field.push(c),
+                }
+            }
+        }
+        fields.push(field);
+    }
+}
+
+/// 把 CSV/TSV 文本解析为聚类型题库的各项
+///
+/// 空行与 `#` 开头的行跳过；出错的行记入错误，不计入结果。`Layout::Rows` 下
+/// 聚类标识按行的先后从 1 编起。
+pub fn parse_clustering(text: &str, layout: Layout) -> (Vec<ClusteringItem>, Vec<LineError>) {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let delim = delimiter(text);
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    let mut clusters = 0;
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let fields: Vec<String> = match split_record(line, delim) {
+            Ok(v) => v.iter().map(|s| s.trim().to_string()).collect(),
+            Err(message) => {
+                errors.push(LineError {
+                    line: n + 1,
+                    message,
+                });
+                continue;
+            }
+        };
+        match layout {
+            Layout::Rows => {
+                let members: Vec<&String> = fields.iter().filter(|s| !s.is_empty()).collect();
+                if members.is_empty() {
+                    continue;
+                }
+                clusters += 1;
+                items.extend(members.into_iter().map(|data| ClusteringItem {
+                    data: data.clone(),
+                    clsid: clusters,
+                    kind: None,
+                }));
+            }
+            Layout::Pairs => match parse_pair(&fields) {
+                Ok(item) => items.push(item),
+                Err(message) => errors.push(LineError {
+                    line: n + 1,
+                    message,
+                }),
+            },
+        }
+    }
+    (items, errors)
+}
+
+/// 解析 `表示,聚类标识[,标签]` 一行
+fn parse_pair(fields: &[String]) -> Result<ClusteringItem, String> {
+    let (data, clsid, kind) = match fields {
+        [data, clsid] => (data, clsid, None),
+        [data, clsid, kind] => (data, clsid, Some(kind).filter(|k| !k.is_empty())),
+        _ => return Err(format!("应有2或3列，实际为{}列", fields.len())),
+    };
+    if data.is_empty() {
+        return Err(String::from("表示为空"));
+    }
+    let clsid = clsid
+        .parse()
+        .map_err(|_| format!("聚类标识“{}”不是整数", clsid))?;
+    Ok(ClusteringItem {
+        data: data.clone(),
+        clsid,
+        kind: kind.cloned(),
+    })
+}
@@ -1,8 +1,17 @@
 use crate::exam;
+use crate::exam::ExamMode;
+use crate::pack::{Manifest, Pack};
+use crate::session::{now_ms, Answer, ExamSession, ExamSessions, PaperSpec};
+use crate::srs::Deck;
+use crate::stats::{self, load_results, load_tagged_results, BankStats, Mistakes};
+use crate::{Config, SharedStore};
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
+use std::sync::Arc;
 use tera::{Context as TeraContext, Tera};
 use tokio::sync::oneshot;
 use warp::Filter;
+use warp::Reply;
 
 macro_rules! load_templates {
     (@inner $v:ident) => {};
@@ -27,11 +36,25 @@ lazy_static::lazy_static! {
         let mut tera = Tera::default();
         let files = load_templates![
             "editor_clustering",
+            "editor_clustering_cards",
+            "editor_clustering_preview",
+            "editor_facet",
+            "editor_hexagram",
+            "editor_sequence",
             "editor_index",
+            "editor_policy",
             "failure",
+            "history_clustering",
             "index",
             "list_history",
+            "stats_clustering",
             "test_clustering",
+            "test_facet",
+            "test_header",
+            "test_matching",
+            "test_multi",
+            "test_sequence",
+            "test_typed",
             "test_name_option",
             "welcome",
             ];
@@ -93,8 +116,8 @@ macro_rules! str_decode {
 }
 
 macro_rules! load_exam {
-    ($typ:expr, $name:expr) => {
-        match super::load_test_data($typ, $name) {
+    ($store:expr, $typ:expr, $name:expr) => {
+        match super::load_test_data($store, $typ, $name) {
             Ok(v) => v,
             Err(e) => {
                 log::error!("load exam data {}/{} error: {}", $typ, $name, &e);
@@ -104,156 +127,1058 @@ macro_rules! load_exam {
     };
 }
 
-#[derive(Deserialize, Serialize)]
-struct SaveHistory {
-    pub typ: String,
-    pub name: String,
-    pub data: String,
+fn with_store(
+    store: SharedStore,
+) -> impl Filter<Extract = (SharedStore,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || store.clone())
 }
 
-pub async fn run_editor(shutdown: oneshot::Receiver<i32>) {
+fn with_config(
+    cfg: Arc<Config>,
+) -> impl Filter<Extract = (Arc<Config>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || cfg.clone())
+}
+
+fn with_sessions(
+    sessions: Arc<ExamSessions>,
+) -> impl Filter<Extract = (Arc<ExamSessions>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || sessions.clone())
+}
+
+/// 出题参数，`seed` 缺省时随机取，`options` 为每题选项数，`mode` 为抽题方式，
+/// `from`/`to` 为有向路型题库的源面与目标面，`heads`/`kinds` 为以逗号分隔的题干与选项标签
+#[derive(Deserialize, Debug)]
+struct ExamQuery {
+    seed: Option<u64>,
+    options: Option<usize>,
+    mode: Option<ExamMode>,
+    from: Option<String>,
+    to: Option<String>,
+    seq: Option<String>,
+    heads: Option<String>,
+    kinds: Option<String>,
+}
+
+impl ExamQuery {
+    fn gen_options(&self) -> exam::GenOptions {
+        let split = |s: &Option<String>| -> Vec<String> {
+            s.iter()
+                .flat_map(|s| s.split(','))
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        let mut gen = exam::GenOptions::default();
+        if let Some(n) = self.options {
+            gen.option_count = n;
+        }
+        gen.head_kinds = split(&self.heads);
+        gen.option_kinds = split(&self.kinds);
+        gen
+    }
+}
+
+/// 导出题库包时清单的版本与作者，缺省为 `1` 与空
+#[derive(Deserialize, Debug)]
+struct PackQuery {
+    version: Option<String>,
+    author: Option<String>,
+}
+
+/// 编辑器提交题库、预览表格的请求体上限
+const BANK_BODY_LIMIT: u64 = 1024 * 1024;
+
+/// 待预览的 CSV/TSV 表格
+#[derive(Deserialize, Debug)]
+struct TableForm {
+    text: String,
+    layout: crate::table::Layout,
+}
+
+/// 重新生成同一份随机试卷的地址；复习、弱项练习与早期记录无法重做
+fn retake_url(typ: &str, name: &str, result: &exam::ExamResult) -> Option<String> {
+    let seed = result.seed?;
+    if matches!(result.mode, ExamMode::Review | ExamMode::Weak) || result.prob_count == 0 {
+        return None;
+    }
+    let enc = |s: &str| urlencoding::encode(s).into_owned();
+    let mut url = format!(
+        "/exam/{}/{}/{}?seed={}&options={}",
+        typ,
+        enc(name),
+        result.prob_count,
+        seed,
+        result.gen.option_count
+    );
+    if result.mode != ExamMode::Random {
+        url += &format!("&mode={:?}", result.mode);
+    }
+    if let Some(dir) = &result.direction {
+        url += &format!("&from={}&to={}", enc(&dir.from), enc(&dir.to));
+    }
+    if let Some(seq) = &result.sequence {
+        url += &format!("&seq={}", enc(seq));
+    }
+    for (key, kinds) in [
+        ("heads", &result.gen.head_kinds),
+        ("kinds", &result.gen.option_kinds),
+    ] {
+        if !kinds.is_empty() {
+            url += &format!("&{}={}", key, enc(&kinds.join(",")));
+        }
+    }
+    Some(url)
+}
+
+/// 连线题一组在作答页上的显示，未作答的组不含答案
+#[derive(Serialize, Debug)]
+struct GridView<'a> {
+    id: i32,
+    targets: &'a [exam::ClusteringExamProbOption],
+    rows: Vec<GridRow<'a>>,
+    done: bool,
+    correct: bool,
+    explain: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct GridRow<'a> {
+    head: &'a str,
+    chosen: Option<i32>,
+    answer: Option<i32>,
+    correct: bool,
+}
+
+fn grid_views(session: &ExamSession) -> Vec<GridView<'_>> {
+    session
+        .grids
+        .iter()
+        .map(|g| {
+            let done = (g.id as usize) < session.answers.len();
+            let rows: Vec<GridRow> = g
+                .heads
+                .iter()
+                .enumerate()
+                .map(|(i, head)| {
+                    let res = session.answers.get(g.id as usize + i).filter(|_| done);
+                    GridRow {
+                        head,
+                        chosen: res.and_then(|r| r.chosen),
+                        answer: res.map(|r| r.answer),
+                        correct: res.is_some_and(|r| r.is_correct()),
+                    }
+                })
+                .collect();
+            GridView {
+                id: g.id,
+                targets: &g.targets,
+                correct: rows.iter().all(|r| r.correct),
+                rows,
+                done,
+                explain: if done { &g.explain } else { "" },
+            }
+        })
+        .collect()
+}
+
+/// 渲染作答页，已作答的题目显示判分结果
+fn render_session(session: &ExamSession) -> warp::reply::Html<String> {
+    let mut ctx = TeraContext::new();
+    ctx.insert("paper_id", &session.id);
+    ctx.insert("seed", &session.seed);
+    ctx.insert("item_count", &session.probs.len());
+    ctx.insert("data", &session.probs);
+    ctx.insert("answers", &session.answers);
+    ctx.insert("policy", &session.policy);
+    ctx.insert("remaining_ms", &session.remaining_ms());
+    ctx.insert("question_remaining_ms", &session.question_remaining_ms());
+    match (&session.direction, session.mode) {
+        (Some(dir), _) => {
+            ctx.insert("direction", dir);
+            // 六十四卦型的题干与选项在出题时已转为 html
+            ctx.insert("raw_html", &(session.typ == "4"));
+            render!("test_facet.html", &ctx)
+        }
+        (None, ExamMode::Next | ExamMode::Prev | ExamMode::Order) => {
+            ctx.insert("mode", &session.mode);
+            ctx.insert("sequence", &session.sequence);
+            ctx.insert("grids", &grid_views(session));
+            render!("test_sequence.html", &ctx)
+        }
+        (None, ExamMode::Typed) => render!("test_typed.html", &ctx),
+        (None, ExamMode::Multi) => {
+            let verdicts: Vec<bool> = session.answers.iter().map(|a| a.is_correct()).collect();
+            ctx.insert("verdicts", &verdicts);
+            render!("test_multi.html", &ctx)
+        }
+        (None, ExamMode::Matching) => {
+            ctx.insert("grids", &grid_views(session));
+            render!("test_matching.html", &ctx)
+        }
+        (None, _) => render!("test_clustering.html", &ctx),
+    }
+}
+
+/// 诊断列表的简短描述
+fn describe_diagnostics(v: &[exam::Diagnostic]) -> String {
+    v.iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>()
+        .join("；")
+}
+
+/// 按 `mode` 为聚类型题库 `name` 出卷并渲染作答页；`count` 缺省时取题库规则的题数
+fn open_clustering(
+    store: &SharedStore,
+    sessions: &ExamSessions,
+    name: &str,
+    count: Option<usize>,
+    q: &ExamQuery,
+    mode: ExamMode,
+) -> warp::reply::Html<String> {
+    let data: exam::ClusteringExam = load_exam!(&**store, "1", name);
+    let count = count.unwrap_or(data.policy.prob_count);
+    if count == 0 {
+        return render!(@errhtml "生成试卷", "题量不能为0");
+    }
+    let errors: Vec<exam::Diagnostic> = data
+        .validate()
+        .into_iter()
+        .filter(|d| d.severity() == exam::Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        return render!(@errhtml "生成试卷", &format!("题库有误：{}", describe_diagnostics(&errors)));
+    }
+    let seed = q.seed.unwrap_or_else(exam::new_seed);
+    let gen = q.gen_options();
+    let mut grids = Vec::new();
+    let probs = match mode {
+        ExamMode::Random => data.gen_probs_seeded(count, &gen, seed),
+        ExamMode::Review => Deck::load(&**store, "1", name).and_then(|deck| {
+            let mut rng = exam::seeded_rng(seed);
+            let heads = deck.pick(&data, &gen, count, now_ms(), &mut rng);
+            data.gen_probs_for_heads(&heads, &gen, None, &mut rng)
+        }),
+        ExamMode::Weak => load_results(&**store, "1", name).and_then(|results| {
+            let weights = Mistakes::from_results(&data, &results);
+            data.gen_probs_weighted(count, &gen, &weights, &mut exam::seeded_rng(seed))
+        }),
+        ExamMode::Typed => data.gen_probs_typed(count, &gen, &mut exam::seeded_rng(seed)),
+        ExamMode::Multi => data.gen_probs_multi(count, &gen, &mut exam::seeded_rng(seed)),
+        // 题量按对数计，凑整为整组
+        ExamMode::Matching => {
+            let n = count.div_ceil(gen.option_count.max(1));
+            data.gen_matching(n, &gen, &mut exam::seeded_rng(seed))
+                .map(|v| grids = v)
+                .map(|_| Vec::new())
+        }
+        ExamMode::Next | ExamMode::Prev | ExamMode::Order => Err("聚类型题库不能按序列出卷".into()),
+    };
+    let probs = match probs {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("generate paper of 1/{} error: {}", name, &e);
+            return render!(@errhtml "生成试卷", &format!("题库无法出题：{}", e));
+        }
+    };
+    if probs.is_empty() && grids.is_empty() {
+        return render!(@errhtml "生成试卷", "题库无法出题：试卷为空");
+    }
+    let spec = PaperSpec {
+        mode,
+        seed,
+        gen,
+        policy: data.policy,
+        direction: None,
+        sequence: None,
+    };
+    let opened = match mode {
+        ExamMode::Matching => sessions.open_matching("1", name, spec, grids),
+        _ => sessions.open("1", name, spec, probs),
+    };
+    match opened {
+        Ok(session) => render_session(&session),
+        Err(e) => render!(@errhtml "生成试卷", &format!("保存试卷错误：{}", e)),
+    }
+}
+
+/// 按 `q` 的方向为有向路型或六十四卦型题库 `typ`/`name` 出卷并渲染作答页；`count` 缺省时取题库规则的题数
+fn open_facet<B: exam::FacetBank + DeserializeOwned>(
+    store: &SharedStore,
+    sessions: &ExamSessions,
+    typ: &str,
+    name: &str,
+    count: Option<usize>,
+    q: &ExamQuery,
+) -> warp::reply::Html<String> {
+    let data: B = load_exam!(&**store, typ, name);
+    let count = count.unwrap_or(data.policy().prob_count);
+    if count == 0 {
+        return render!(@errhtml "生成试卷", "题量不能为0");
+    }
+    let errors: Vec<exam::Diagnostic> = data
+        .diagnose()
+        .into_iter()
+        .filter(|d| d.severity() == exam::Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        return render!(@errhtml "生成试卷", &format!("题库有误：{}", describe_diagnostics(&errors)));
+    }
+    if q.mode.unwrap_or_default() != ExamMode::Random {
+        return render!(@errhtml "生成试卷", "该题库只能随机出卷");
+    }
+    let direction = match (&q.from, &q.to) {
+        (Some(from), Some(to)) => exam::Direction {
+            from: from.clone(),
+            to: to.clone(),
+        },
+        _ => return render!(@errhtml "生成试卷", "需要指定源面与目标面"),
+    };
+    let seed = q.seed.unwrap_or_else(exam::new_seed);
+    let gen = q.gen_options();
+    let probs = match data.gen_directed(count, &direction, &gen, seed) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("generate paper of {}/{} error: {}", typ, name, &e);
+            return render!(@errhtml "生成试卷", &format!("题库无法出题：{}", e));
+        }
+    };
+    if probs.is_empty() {
+        return render!(@errhtml "生成试卷", "题库无法出题：试卷为空");
+    }
+    let spec = PaperSpec {
+        mode: ExamMode::Random,
+        seed,
+        gen,
+        policy: data.policy(),
+        direction: Some(direction),
+        sequence: None,
+    };
+    match sessions.open(typ, name, spec, probs) {
+        Ok(session) => render_session(&session),
+        Err(e) => render!(@errhtml "生成试卷", &format!("保存试卷错误：{}", e)),
+    }
+}
+
+/// 按 `q` 的序列与 `mode` 为序列型题库 `name` 出卷并渲染作答页；`count` 缺省时取题库规则的题数
+fn open_sequence(
+    store: &SharedStore,
+    sessions: &ExamSessions,
+    name: &str,
+    count: Option<usize>,
+    q: &ExamQuery,
+) -> warp::reply::Html<String> {
+    let data: exam::SequenceExam = load_exam!(&**store, "3", name);
+    let count = count.unwrap_or(data.policy.prob_count);
+    if count == 0 {
+        return render!(@errhtml "生成试卷", "题量不能为0");
+    }
+    let errors: Vec<exam::Diagnostic> = data
+        .validate()
+        .into_iter()
+        .filter(|d| d.severity() == exam::Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        return render!(@errhtml "生成试卷", &format!("题库有误：{}", describe_diagnostics(&errors)));
+    }
+    let sequence = match q.seq.as_ref().or(data.sequences.first().map(|s| &s.name)) {
+        Some(s) => s.clone(),
+        None => return render!(@errhtml "生成试卷", "题库没有序列"),
+    };
+    let seed = q.seed.unwrap_or_else(exam::new_seed);
+    let gen = q.gen_options();
+    let mode = q.mode.unwrap_or(ExamMode::Next);
+    let mut rng = exam::seeded_rng(seed);
+    let mut grids = Vec::new();
+    let probs = match mode {
+        ExamMode::Next => data.gen_probs_adjacent(count, &sequence, true, &gen, &mut rng),
+        ExamMode::Prev => data.gen_probs_adjacent(count, &sequence, false, &gen, &mut rng),
+        // 题量按项数计，凑整为整组
+        ExamMode::Order => {
+            let n = count.div_ceil(gen.option_count.max(1));
+            data.gen_order(n, &sequence, &gen, &mut rng)
+                .map(|v| grids = v)
+                .map(|_| Vec::new())
+        }
+        _ => return render!(@errhtml "生成试卷", "序列型题库只能问前后项或排序"),
+    };
+    let probs = match probs {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("generate paper of 3/{} error: {}", name, &e);
+            return render!(@errhtml "生成试卷", &format!("题库无法出题：{}", e));
+        }
+    };
+    if probs.is_empty() && grids.is_empty() {
+        return render!(@errhtml "生成试卷", "题库无法出题：试卷为空");
+    }
+    let spec = PaperSpec {
+        mode,
+        seed,
+        gen,
+        policy: data.policy,
+        direction: None,
+        sequence: Some(sequence),
+    };
+    let opened = match mode {
+        ExamMode::Order => sessions.open_matching("3", name, spec, grids),
+        _ => sessions.open("3", name, spec, probs),
+    };
+    match opened {
+        Ok(session) => render_session(&session),
+        Err(e) => render!(@errhtml "生成试卷", &format!("保存试卷错误：{}", e)),
+    }
+}
+
+/// 各类型题库的出卷入口，由 `by_bank_type!` 按题库类型分派
+trait OpenExam {
+    fn open_exam(
+        store: &SharedStore,
+        sessions: &ExamSessions,
+        typ: &str,
+        name: &str,
+        count: Option<usize>,
+        q: &ExamQuery,
+    ) -> warp::reply::Html<String>;
+}
+
+impl OpenExam for exam::ClusteringExam {
+    fn open_exam(
+        store: &SharedStore,
+        sessions: &ExamSessions,
+        _typ: &str,
+        name: &str,
+        count: Option<usize>,
+        q: &ExamQuery,
+    ) -> warp::reply::Html<String> {
+        open_clustering(store, sessions, name, count, q, q.mode.unwrap_or_default())
+    }
+}
+
+impl OpenExam for exam::FacetExam {
+    fn open_exam(
+        store: &SharedStore,
+        sessions: &ExamSessions,
+        typ: &str,
+        name: &str,
+        count: Option<usize>,
+        q: &ExamQuery,
+    ) -> warp::reply::Html<String> {
+        open_facet::<Self>(store, sessions, typ, name, count, q)
+    }
+}
+
+impl OpenExam for exam::SequenceExam {
+    fn open_exam(
+        store: &SharedStore,
+        sessions: &ExamSessions,
+        _typ: &str,
+        name: &str,
+        count: Option<usize>,
+        q: &ExamQuery,
+    ) -> warp::reply::Html<String> {
+        open_sequence(store, sessions, name, count, q)
+    }
+}
+
+impl OpenExam for exam::HexagramExam {
+    fn open_exam(
+        store: &SharedStore,
+        sessions: &ExamSessions,
+        typ: &str,
+        name: &str,
+        count: Option<usize>,
+        q: &ExamQuery,
+    ) -> warp::reply::Html<String> {
+        open_facet::<Self>(store, sessions, typ, name, count, q)
+    }
+}
+
+/// 按题库类型 `typ` 为题库 `name` 出卷并渲染作答页
+fn open_exam(
+    store: &SharedStore,
+    sessions: &ExamSessions,
+    typ: &str,
+    name: &str,
+    count: Option<usize>,
+    q: &ExamQuery,
+) -> warp::reply::Html<String> {
+    let open = || -> crate::Result<warp::reply::Html<String>> {
+        Ok(by_bank_type!(typ, V => V::open_exam(store, sessions, typ, name, count, q)))
+    };
+    match open() {
+        Ok(page) => page,
+        Err(e) => render!(@errhtml "生成试卷", &format!("题库类型错误：{}", e)),
+    }
+}
+
+/// 聚类型题库 `name` 的作答统计
+fn bank_stats(store: &SharedStore, name: &str) -> crate::Result<BankStats> {
+    let bank: exam::ClusteringExam = super::load_test_data(&**store, "1", name)?;
+    Ok(BankStats::new(&bank, &load_results(&**store, "1", name)?))
+}
+
+fn card_testnameopts(
+    store: SharedStore,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("test_name_option" / String)
+        .and(with_store(store))
+        .map(|typ: String, store: SharedStore| {
+            let mut ctx = TeraContext::new();
+            let names = store.list_banks(&typ).unwrap_or_else(|e| {
+                log::error!("list tests of type {} error: {}", &typ, e);
+                Default::default()
+            });
+            ctx.insert("opts", &names);
+            render!("test_name_option.html", &ctx)
+        })
+}
+
+pub async fn run_editor(shutdown: oneshot::Receiver<i32>, cfg: Arc<Config>, store: SharedStore) {
     let index = warp::path::end().map(|| render!("editor_index.html", &TeraContext::new()));
 
     let editor = warp::path("editor");
-    let editor_clustering = warp::path!("1" / String).map(|name: String| {
-        str_decode!(name);
-        let mut ctx = TeraContext::new();
-        let data: exam::ClusteringExam = super::load_test_data("1", &name).unwrap_or_else(|e| {
-            log::error!("load ClusteringExam of {} error: {}", name, e);
-            Default::default()
+    let editor_clustering = warp::path!("1" / String)
+        .and(with_store(store.clone()))
+        .map(|name: String, store: SharedStore| {
+            str_decode!(name);
+            let mut ctx = TeraContext::new();
+            let data: exam::ClusteringExam = super::load_test_data(&*store, "1", &name)
+                .unwrap_or_else(|e| {
+                    log::error!("load ClusteringExam of {} error: {}", name, e);
+                    Default::default()
+                });
+            let col = data.column_count();
+            let tbl = data.table();
+            ctx.insert("data", &tbl);
+            ctx.insert("column", &col);
+            ctx.insert("policy", &data.policy);
+            ctx.insert("spellings", &data.spellings);
+            render!("editor_clustering.html", &ctx)
+        });
+
+    let editor_facet = warp::path!("2" / String)
+        .and(with_store(store.clone()))
+        .map(|name: String, store: SharedStore| {
+            str_decode!(name);
+            let mut ctx = TeraContext::new();
+            let data: exam::FacetExam =
+                super::load_test_data(&*store, "2", &name).unwrap_or_else(|e| {
+                    log::error!("load FacetExam of {} error: {}", name, e);
+                    Default::default()
+                });
+            ctx.insert("facets", &data.facets);
+            ctx.insert("items", &data.items);
+            ctx.insert("policy", &data.policy);
+            render!("editor_facet.html", &ctx)
+        });
+
+    let editor_hexagram = warp::path!("4" / String)
+        .and(with_store(store.clone()))
+        .map(|name: String, store: SharedStore| {
+            str_decode!(name);
+            let mut ctx = TeraContext::new();
+            let data: exam::HexagramExam = super::load_test_data(&*store, "4", &name)
+                .unwrap_or_else(|e| {
+                    log::error!("load HexagramExam of {} error: {}", name, e);
+                    Default::default()
+                });
+            let figures: Vec<String> = data
+                .hexagrams
+                .iter()
+                .map(|h| exam::hexagram_svg(h.lines.trim()))
+                .collect();
+            ctx.insert("hexagrams", &data.hexagrams);
+            ctx.insert("figures", &figures);
+            ctx.insert("policy", &data.policy);
+            render!("editor_hexagram.html", &ctx)
+        });
+
+    let editor_sequence = warp::path!("3" / String)
+        .and(with_store(store.clone()))
+        .map(|name: String, store: SharedStore| {
+            str_decode!(name);
+            let mut ctx = TeraContext::new();
+            let data: exam::SequenceExam = super::load_test_data(&*store, "3", &name)
+                .unwrap_or_else(|e| {
+                    log::error!("load SequenceExam of {} error: {}", name, e);
+                    Default::default()
+                });
+            ctx.insert("sequences", &data.sequences);
+            ctx.insert("policy", &data.policy);
+            render!("editor_sequence.html", &ctx)
+        });
+
+    let preview_clustering = warp::path!("preview" / "1")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(BANK_BODY_LIMIT))
+        .and(warp::body::json())
+        .map(|form: TableForm| {
+            let (data, errors) = crate::table::parse_clustering(&form.text, form.layout);
+            let bank = exam::ClusteringExam {
+                data,
+                ..Default::default()
+            };
+            let mut ctx = TeraContext::new();
+            ctx.insert("data", &bank.table());
+            ctx.insert("count", &bank.data.len());
+            ctx.insert("errors", &errors);
+            let diags = if bank.data.is_empty() {
+                String::new()
+            } else {
+                describe_diagnostics(&bank.validate())
+            };
+            ctx.insert("diagnostics", &diags);
+            render!("editor_clustering_preview.html", &ctx)
         });
-        let col = data.column_count();
-        let tbl = data.table();
-        ctx.insert("data", &tbl);
-        ctx.insert("column", &col);
-        render!("editor_clustering.html", &ctx)
-    });
 
     let card = warp::path("card");
-    let card_testnameopts = warp::path!("test_name_option" / String).map(|typ| {
-        let mut ctx = TeraContext::new();
-        let names = super::list_names_of_test_type(typ);
-        ctx.insert("opts", &names);
-        render!("test_name_option.html", &ctx)
-    });
 
     let submit = warp::path("submit");
-    let submit_clustering = warp::path!("1" / String)
+    let submit_bank = warp::path!(String / String)
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 16))
-        .and(warp::body::json())
-        .map(|name: String, opt: exam::ClusteringExam| {
+        .and(warp::body::content_length_limit(BANK_BODY_LIMIT))
+        .and(warp::body::bytes())
+        .and(with_store(store.clone()))
+        .map(
+            |typ: String, name: String, body: warp::hyper::body::Bytes, store: SharedStore| {
+                str_decode!(@jsn name);
+                let desc = match super::submit_bank(&*store, &typ, &name, &body) {
+                    Ok(diags) => {
+                        log::info!("save test {}/{}", &typ, &name);
+                        if diags.is_empty() {
+                            String::from("保存成功")
+                        } else {
+                            format!("保存成功，但题库有问题：{}", describe_diagnostics(&diags))
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("save test {}/{} error: {}", &typ, &name, &e);
+                        format!("保存出错：{}", e)
+                    }
+                };
+                warp::reply::json(&desc)
+            },
+        );
+
+    let validate = warp::path!("validate" / String / String)
+        .and(with_store(store.clone()))
+        .map(|typ: String, name: String, store: SharedStore| {
             str_decode!(@jsn name);
-            let desc = match super::commit_test_data("1", &name, &opt) {
-                Ok(_) => {
-                    log::info!("save test type clustering {}", &name);
-                    String::from("保存成功")
-                }
-                Err(e) => {
-                    log::error!("save test type clustering {} error: {}", &name, &e);
-                    format!("保存出错：{}", e)
-                }
+            let diags = match super::validate_bank(&*store, &typ, &name) {
+                Ok(v) => v,
+                Err(e) => return warp::reply::json(&format!("加载数据错误：{}", e)),
             };
-            warp::reply::json(&desc)
+            let v: Vec<serde_json::Value> = diags
+                .iter()
+                .map(|d| {
+                    serde_json::json!({
+                        "severity": d.severity(),
+                        "message": d.to_string(),
+                        "detail": d,
+                    })
+                })
+                .collect();
+            warp::reply::json(&v)
         });
 
-    let static_files = warp::path("static").and(warp::fs::dir(var_path!(@static)));
+    let export = warp::path!("export" / String / String)
+        .and(warp::query::<PackQuery>())
+        .and(with_config(cfg.clone()))
+        .and(with_store(store.clone()))
+        .map(
+            |typ: String, name: String, q: PackQuery, cfg: Arc<Config>, store: SharedStore| {
+                let name = str_decode!(@assign name, e, warp::reply::json(
+                    &format!("URLDecode错误：{}", &e)
+                ).into_response())
+                .into_owned();
+                let manifest = Manifest {
+                    name: name.clone(),
+                    version: q.version.unwrap_or_else(|| String::from("1")),
+                    typ,
+                    author: q.author.unwrap_or_default(),
+                };
+                let data = Pack::export(&cfg, &*store, manifest, std::slice::from_ref(&name))
+                    .and_then(|pack| Ok(ron::ser::to_string(&pack)?));
+                match data {
+                    Ok(data) => warp::reply::with_header(
+                        data,
+                        "content-disposition",
+                        format!(
+                            "attachment; filename*=UTF-8''{}.elpack",
+                            urlencoding::encode(&name)
+                        ),
+                    )
+                    .into_response(),
+                    Err(e) => {
+                        log::error!("export pack {} error: {}", &name, &e);
+                        warp::reply::json(&format!("导出出错：{}", e)).into_response()
+                    }
+                }
+            },
+        );
+
+    let import = warp::path!("import")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 1024 * 16))
+        .and(warp::body::bytes())
+        .and(with_config(cfg.clone()))
+        .and(with_store(store.clone()))
+        .map(
+            |body: warp::hyper::body::Bytes, cfg: Arc<Config>, store: SharedStore| {
+                let report = std::str::from_utf8(&body)
+                    .map_err(|e| crate::Error::from(e.to_string()))
+                    .and_then(|s| Ok(ron::de::from_str::<Pack>(s)?))
+                    .and_then(|pack| Ok((pack.import(&cfg, &*store)?, pack)));
+                let desc = match report {
+                    Ok((r, pack)) if r.skipped.is_empty() => format!(
+                        "导入{} {}：{}个题库、{}个媒体文件",
+                        &pack.manifest.name, &pack.manifest.version, r.banks, r.media
+                    ),
+                    Ok((r, pack)) => format!(
+                        "导入{} {}：{}个题库、{}个媒体文件；已有内容不同的题库未覆盖：{}",
+                        &pack.manifest.name,
+                        &pack.manifest.version,
+                        r.banks,
+                        r.media,
+                        r.skipped.join("、")
+                    ),
+                    Err(e) => {
+                        log::error!("import pack error: {}", &e);
+                        format!("导入出错：{}", e)
+                    }
+                };
+                warp::reply::json(&desc)
+            },
+        );
+
+    let delete = warp::path!("delete" / String / String)
+        .and(warp::post())
+        .and(with_config(cfg.clone()))
+        .and(with_store(store.clone()))
+        .map(
+            |typ: String, name: String, cfg: Arc<Config>, store: SharedStore| {
+                str_decode!(@jsn name);
+                let desc = match store.delete_bank(&typ, &name) {
+                    Ok(_) => {
+                        log::info!("delete test {}/{}", &typ, &name);
+                        if let Err(e) = super::forget_pack(&cfg, &typ, &name) {
+                            log::error!("record deletion of {}/{} error: {}", &typ, &name, &e);
+                        }
+                        String::from("删除成功")
+                    }
+                    Err(e) => {
+                        log::error!("delete test {}/{} error: {}", &typ, &name, &e);
+                        format!("删除出错：{}", e)
+                    }
+                };
+                warp::reply::json(&desc)
+            },
+        );
+
+    let static_files = warp::path("static").and(warp::fs::dir(cfg.static_dir()));
     let favicon =
-        warp::path!("favicon.ico").and(warp::fs::file(concat!(var_path!(@static), "/favicon.ico")));
+        warp::path!("favicon.ico").and(warp::fs::file(cfg.static_dir().join("favicon.ico")));
 
     let app = index
-        .or(editor.and(editor_clustering))
-        .or(card.and(card_testnameopts))
-        .or(submit.and(submit_clustering))
+        .or(editor.and(
+            editor_clustering
+                .or(editor_facet)
+                .or(editor_sequence)
+                .or(editor_hexagram),
+        ))
+        .or(card.and(card_testnameopts(store.clone())))
+        .or(submit.and(submit_bank))
+        .or(validate)
+        .or(preview_clustering)
+        .or(export)
+        .or(import)
+        .or(delete)
         .or(static_files)
         .or(favicon);
 
     log::info!("www running");
-    let (_, run) = warp::serve(app).bind_with_graceful_shutdown(([0, 0, 0, 0], 3732), async move {
-        shutdown.await.ok();
-        log::info!("graceful shutdown");
-    });
+    let (_, run) = warp::serve(app).bind_with_graceful_shutdown(
+        ([0, 0, 0, 0], cfg.port.unwrap_or(3732)),
+        async move {
+            shutdown.await.ok();
+            log::info!("graceful shutdown");
+        },
+    );
     run.await;
     log::info!("www stopped");
 }
 
-pub async fn run(shutdown: oneshot::Receiver<i32>) {
+pub async fn run(shutdown: oneshot::Receiver<i32>, cfg: Arc<Config>, store: SharedStore) {
+    let sessions = Arc::new(ExamSessions::new(store.clone(), cfg.session_ttl));
+    {
+        let sessions = sessions.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(600));
+            loop {
+                interval.tick().await;
+                match sessions.expire() {
+                    Ok(0) => {}
+                    Ok(n) => log::info!("expired {} paper(s)", n),
+                    Err(e) => log::error!("expire papers error: {}", e),
+                }
+            }
+        });
+    }
+
     let index = warp::path::end().map(|| render!("index.html", &TeraContext::new()));
 
     let card = warp::path("card");
-    let card_testnameopts = warp::path!("test_name_option" / String).map(|typ| {
-        let mut ctx = TeraContext::new();
-        let names = super::list_names_of_test_type(typ);
-        ctx.insert("opts", &names);
-        render!("test_name_option.html", &ctx)
-    });
-
-    let welcome = warp::path!("welcome" / String / String).map(|typ, name| {
-        let mut ctx = TeraContext::new();
-        ctx.insert("type", &typ);
-        ctx.insert("name", &name);
-        render!("welcome.html", &ctx)
-    });
+
+    let welcome = warp::path!("welcome" / String / String)
+        .and(with_store(store.clone()))
+        .and(with_sessions(sessions.clone()))
+        .map(
+            |typ: String, name: String, store: SharedStore, sessions: Arc<ExamSessions>| {
+                let mut ctx = TeraContext::new();
+                let (attempts, due, facets, kinds, sequences) = {
+                    let name =
+                        str_decode!(@assign name, e, render!(@errhtml "URLDecode", &e.to_string()));
+                    let attempts = sessions.unfinished(&typ, &name).unwrap_or_else(|e| {
+                        log::error!("list unfinished papers of {}/{} error: {}", &typ, &name, e);
+                        Default::default()
+                    });
+                    let (mut due, mut facets, mut kinds, mut sequences) =
+                        (0, Vec::new(), Vec::new(), Vec::new());
+                    let loaded = match typ.as_str() {
+                        "1" => super::load_test_data::<exam::ClusteringExam>(&*store, &typ, &name)
+                            .and_then(|bank| {
+                                due = Deck::load(&*store, &typ, &name)?.due_count(&bank, now_ms());
+                                kinds = bank.kinds().into_iter().map(String::from).collect();
+                                Ok(())
+                            }),
+                        "2" => super::load_test_data::<exam::FacetExam>(&*store, &typ, &name)
+                            .map(|bank| facets = bank.facets),
+                        "4" => super::load_test_data::<exam::HexagramExam>(&*store, &typ, &name)
+                            .map(|_| {
+                                facets = exam::HEXAGRAM_FACETS
+                                    .iter()
+                                    .map(|f| f.to_string())
+                                    .collect()
+                            }),
+                        "3" => super::load_test_data::<exam::SequenceExam>(&*store, &typ, &name)
+                            .map(|bank| {
+                                sequences = bank.sequences.into_iter().map(|s| s.name).collect()
+                            }),
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = loaded {
+                        log::error!("load bank {}/{} error: {}", &typ, &name, e);
+                    }
+                    (attempts, due, facets, kinds, sequences)
+                };
+                ctx.insert("type", &typ);
+                ctx.insert("name", &name);
+                ctx.insert("attempts", &attempts);
+                ctx.insert("due", &due);
+                ctx.insert("facets", &facets);
+                ctx.insert("kinds", &kinds);
+                ctx.insert("sequences", &sequences);
+                render!("welcome.html", &ctx)
+            },
+        );
 
     let exam = warp::path("exam");
-    let exam_clustering = warp::path!("1" / String / usize).map(|name: String, count| {
-        str_decode!(name);
-        let data: exam::ClusteringExam = load_exam!("1", &name);
-        let data = data.gen_probs(count);
-        let mut ctx = TeraContext::new();
-        ctx.insert("item_count", &count);
-        ctx.insert("data", &data);
-        render!("test_clustering.html", &ctx)
-    });
-
-    let save_history = warp::path!("save_history")
+    let exam_bank = warp::path!(String / String / usize)
+        .and(warp::query::<ExamQuery>())
+        .and(with_store(store.clone()))
+        .and(with_sessions(sessions.clone()))
+        .map(
+            |typ: String,
+             name: String,
+             count,
+             q: ExamQuery,
+             store: SharedStore,
+             sessions: Arc<ExamSessions>| {
+                str_decode!(name);
+                open_exam(&store, &sessions, &typ, &name, Some(count), &q)
+            },
+        );
+
+    let exam_bank_default = warp::path!(String / String)
+        .and(warp::query::<ExamQuery>())
+        .and(with_store(store.clone()))
+        .and(with_sessions(sessions.clone()))
+        .map(
+            |typ: String,
+             name: String,
+             q: ExamQuery,
+             store: SharedStore,
+             sessions: Arc<ExamSessions>| {
+                str_decode!(name);
+                open_exam(&store, &sessions, &typ, &name, None, &q)
+            },
+        );
+
+    let review = warp::path!("review" / "1" / String / usize)
+        .and(warp::query::<ExamQuery>())
+        .and(with_store(store.clone()))
+        .and(with_sessions(sessions.clone()))
+        .map(
+            |name: String, count, q: ExamQuery, store: SharedStore, sessions: Arc<ExamSessions>| {
+                str_decode!(name);
+                open_clustering(&store, &sessions, &name, Some(count), &q, ExamMode::Review)
+            },
+        );
+
+    let resume = warp::path!("resume" / String)
+        .and(with_sessions(sessions.clone()))
+        .map(
+            |id: String, sessions: Arc<ExamSessions>| match sessions.resume(&id) {
+                Ok(session) => render_session(&session),
+                Err(e) => render!(@errhtml "继续测试", &format!("加载试卷错误：{}", e)),
+            },
+        );
+
+    let answer = warp::path!("answer")
         .and(warp::post())
-        .and(warp::body::content_length_limit(1024 * 1024 * 15))
+        .and(warp::body::content_length_limit(1024 * 16))
         .and(warp::body::json())
-        .map(|opt: SaveHistory| {
-            let name = str_decode!(@ajsn opt.name);
-            let desc = match super::commit_history(&opt.typ, &name, &opt.data) {
-                Ok(_) => String::from("保存成功"),
-                Err(e) => {
-                    log::error!("commit history of {}/{} error: {}", &opt.typ, &name, &e);
-                    format!("保存历史出错：{}", e)
-                }
-            };
-            warp::reply::json(&desc)
+        .and(with_sessions(sessions.clone()))
+        .map(|ans: Answer, sessions: Arc<ExamSessions>| {
+            let reply = sessions.answer(&ans);
+            if let Err(e) = &reply {
+                log::error!("answer {:?} error: {}", &ans, e);
+            }
+            warp::reply::json(&reply)
         });
 
-    let list_history =
-        warp::path!("list_history" / String / String).map(|typ: String, name: String| {
+    let list_history = warp::path!("list_history" / String / String)
+        .and(with_store(store.clone()))
+        .map(|typ: String, name: String, store: SharedStore| {
             str_decode!(name);
             str_decode!(typ);
             let mut ctx = TeraContext::new();
-            let mut v = super::list_history_of_kind(&typ, &name);
-            v.sort();
-            ctx.insert("data", &v);
+            let v = store.list_history(&typ, &name).unwrap_or_else(|e| {
+                log::error!("list history of {}/{} error: {}", &typ, &name, e);
+                Default::default()
+            });
+            let points = load_tagged_results(&*store, &typ, &name)
+                .map(|v| stats::trend(&v))
+                .unwrap_or_else(|e| {
+                    log::error!("load results of {}/{} error: {}", &typ, &name, e);
+                    Default::default()
+                });
+            // 无法解析的早期记录不在走势中，仍以按钮列出
+            let legacy: Vec<&String> = v
+                .iter()
+                .filter(|tag| !points.iter().any(|p| &p.tag == *tag))
+                .collect();
+            ctx.insert("data", &legacy);
+            ctx.insert("trend", &points);
             render!("list_history.html", &ctx)
         });
 
-    let static_history = warp::path("history").and(warp::fs::dir(super::history_root()));
+    let trend = warp::path!("trend" / String / String)
+        .and(with_store(store.clone()))
+        .map(|typ: String, name: String, store: SharedStore| {
+            str_decode!(@jsn name);
+            str_decode!(@jsn typ);
+            match load_tagged_results(&*store, &typ, &name) {
+                Ok(v) => warp::reply::json(&stats::trend(&v)),
+                Err(e) => warp::reply::json(&format!("加载历史错误：{}", e)),
+            }
+        });
+
+    let stats = warp::path!("stats" / "1" / String)
+        .and(with_store(store.clone()))
+        .map(|name: String, store: SharedStore| {
+            str_decode!(name);
+            let stats = match bank_stats(&store, &name) {
+                Ok(v) => v,
+                Err(e) => return render!(@errhtml "统计", &format!("统计出错：{}", e)),
+            };
+            let mut ctx = TeraContext::new();
+            ctx.insert("stats", &stats);
+            render!("stats_clustering.html", &ctx)
+        });
+
+    let stats_json = warp::path!("stats" / "1" / String / "json")
+        .and(with_store(store.clone()))
+        .map(|name: String, store: SharedStore| {
+            str_decode!(@jsn name);
+            match bank_stats(&store, &name) {
+                Ok(v) => warp::reply::json(&v),
+                Err(e) => warp::reply::json(&format!("统计出错：{}", e)),
+            }
+        });
+
+    let history = warp::path!("history" / String / String / String)
+        .and(with_store(store.clone()))
+        .map(
+            |typ: String, name: String, tag: String, store: SharedStore| {
+                str_decode!(name);
+                str_decode!(tag);
+                let tag = tag.strip_suffix(".html").unwrap_or(&tag);
+                let data = match store.load_history(&typ, &name, tag) {
+                    Ok(v) => v,
+                    Err(e) => return render!(@errhtml "加载历史", &format!("加载历史错误：{}", e)),
+                };
+                match ron::de::from_str::<exam::ExamResult>(&data) {
+                    Ok(result) => {
+                        let mut ctx = TeraContext::new();
+                        ctx.insert("typ", &typ);
+                        ctx.insert("name", &name);
+                        ctx.insert("result", &result);
+                        ctx.insert("wrong", &result.wrong_count());
+                        let verdicts: Vec<bool> =
+                            result.probs.iter().map(|p| p.is_correct()).collect();
+                        ctx.insert("verdicts", &verdicts);
+                        ctx.insert("raw_html", &(typ == "4"));
+                        ctx.insert("retake", &retake_url(&typ, &name, &result));
+                        render!("history_clustering.html", &ctx)
+                    }
+                    // 早期版本直接保存的作答页面
+                    Err(_) => warp::reply::html(data),
+                }
+            },
+        );
 
-    let static_files = warp::path("static").and(warp::fs::dir(var_path!(@static)));
+    let static_files = warp::path("static").and(warp::fs::dir(cfg.static_dir()));
+    let media_files = warp::path("media")
+        .and(warp::path::peek())
+        .and_then(|p: warp::path::Peek| async move {
+            if crate::pack::is_media(p.as_str()) {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+        .and(warp::fs::dir(cfg.media_dir()));
     let favicon =
-        warp::path!("favicon.ico").and(warp::fs::file(concat!(var_path!(@static), "/favicon.ico")));
+        warp::path!("favicon.ico").and(warp::fs::file(cfg.static_dir().join("favicon.ico")));
 
     let app = index
         .or(welcome)
-        .or(card.and(card_testnameopts))
-        .or(exam.and(exam_clustering))
-        .or(save_history)
+        .or(card.and(card_testnameopts(store.clone())))
+        .or(exam.and(exam_bank.or(exam_bank_default)))
+        .or(review)
+        .or(resume)
+        .or(answer)
         .or(list_history)
-        .or(static_history)
+        .or(trend)
+        .or(history)
+        .or(stats)
+        .or(stats_json)
         .or(static_files)
+        .or(media_files)
         .or(favicon);
 
     log::info!("www running");
-    let (_, run) = warp::serve(app).bind_with_graceful_shutdown(([0, 0, 0, 0], 3733), async move {
-        shutdown.await.ok();
-        log::info!("graceful shutdown");
-    });
+    let (_, run) = warp::serve(app).bind_with_graceful_shutdown(
+        ([0, 0, 0, 0], cfg.port.unwrap_or(3733)),
+        async move {
+            shutdown.await.ok();
+            log::info!("graceful shutdown");
+        },
+    );
     run.await;
     log::info!("www stopped");
 }
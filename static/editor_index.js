@@ -74,6 +74,67 @@ function on_new_test() {
         });
 }
 
+function on_delete_test() {
+    var typ = cur_test_typeid();
+    var name = cur_test_name();
+    if (typ == 0 || name == '未选中') {
+        use_yorn_modal('警告', '<span class="text-danger">需要先选择测试</span>');
+        return;
+    }
+    use_yorn_modal('删除测试', '确认<span class="text-danger">删除</span>测试' + name + '？', function() {
+        do_post_json('/delete/' + typ + '/' + name, {}, function(d) {
+            console.log(d);
+            on_test_type_changed();
+        });
+    });
+}
+
+function on_export_pack() {
+    var typ = cur_test_typeid();
+    var name = cur_test_name();
+    if (typ == 0 || name == '未选中') {
+        use_yorn_modal('警告', '<span class="text-danger">需要先选择测试</span>');
+        return;
+    }
+    use_yorn_modal('导出题库包',
+        '<div class="input-group mb-1">' +
+        '<span class="input-group-text">版本</span>' +
+        '<input id="input-pack-version" type="text" class="form-control" value="1">' +
+        '</div>' +
+        '<div class="input-group">' +
+        '<span class="input-group-text">作者</span>' +
+        '<input id="input-pack-author" type="text" class="form-control" placeholder="可不填">' +
+        '</div>', function() {
+            window.location.href = '/export/' + typ + '/' + encodeURIComponent(name) +
+                '?version=' + encodeURIComponent($('input#input-pack-version').val()) +
+                '&author=' + encodeURIComponent($('input#input-pack-author').val());
+        });
+}
+
+function on_import_pack() {
+    use_yorn_modal('导入题库包',
+        '<input id="input-pack-file" type="file" class="form-control" accept=".elpack">' +
+        '<p class="text-muted small mt-1 mb-0">内容不同的同名题库不会被覆盖</p>', function() {
+            var file = $('input#input-pack-file')[0].files[0];
+            if (!file) return;
+            var reader = new FileReader();
+            reader.onload = function() {
+                $.ajax({
+                    type: "post",
+                    url: '/import',
+                    dataType : "json",
+                    contentType : "text/plain; charset=utf-8",
+                    data: reader.result,
+                    success: function(d) {
+                        use_yorn_modal('导入题库包', $('<span>').text(d).html());
+                        on_test_type_changed();
+                    },
+                });
+            };
+            reader.readAsText(file);
+        });
+}
+
 function use_yorn_modal(title, desc, cb) {
     $('div.modal#yes-or-no-modal h5.modal-title').text(title);
     $('div.modal#yes-or-no-modal div.modal-body').html(desc);
@@ -96,6 +157,7 @@ function on_clustering_new_col(id) {
     input.val('');
     $('div#data-container div.card#data-card-' + id + ' div.d-flex').append('<div class="p-2 ms-1" aria-label="' + data + '">' +
         '<span>' + data + '</span>' +
+        '<input type="text" class="form-control form-control-sm item-kind" placeholder="标签">' +
         '<button type="button" class="btn-close" aria-label="Delete" onclick="clustering_remove_item(\'' + data + '\')"></button>' +
         '</div>');
 }
@@ -136,33 +198,188 @@ function clustering_current_data() {
     for (i = 0; i < q.length; ++i) {
         var cid = q[i].getAttribute('id');
         var id = parseInt(cid.replace('data-card-', ''));
-        var p = $('div#data-container div.card#' + cid + ' div.d-flex div[aria-label] span');
+        var p = $('div#data-container div.card#' + cid + ' div.d-flex div[aria-label]');
         for (j = 0; j < p.length; ++j) {
+            var kind = $(p[j]).find('input.item-kind').val().trim();
             result.push({
                 clsid: id,
-                data: p[j].getInnerHTML(),
+                data: $(p[j]).find('span').text(),
+                kind: kind == '' ? null : kind,
             });
         }
     }
     return result;
 }
 
-function clustering_submit() {
+function clustering_current_policy() {
+    return {
+        prob_count: parseInt($('input#policy-prob-count').val()) || 100,
+        max_wrong: parseInt($('input#policy-max-wrong').val()) || 0,
+        time_limit_secs: parseInt($('input#policy-time-limit').val()) || 0,
+        question_limit_secs: parseInt($('input#policy-question-limit').val()) || 0,
+        allow_surrender: $('input#policy-allow-surrender').prop('checked'),
+    };
+}
+
+function clustering_current_spellings() {
+    return $('textarea#spellings-input').val().split('\n').map(function(line) {
+        return line.split(/\s+/).filter(function(s) {
+            return s != '';
+        });
+    }).filter(function(g) {
+        return g.length > 1;
+    });
+}
+
+function clustering_current_bank() {
+    return {
+        data: clustering_current_data(),
+        policy: clustering_current_policy(),
+        spellings: clustering_current_spellings(),
+    };
+}
+
+function submit_bank(typ, current_bank) {
     $("div#loading-spinner").show();
-    do_post_json('/submit/1/' + cur_test_name(),
-        {
-            data: clustering_current_data(),
-        }, function(d) {
+    do_post_json('/submit/' + typ + '/' + cur_test_name(), current_bank(), function(d) {
             var desc = String(d) + ' - ' + new Date().toLocaleString();
             console.log(desc);
             $('span#clustering-last-submit-time').text(desc);
         }, function() {
-            console.log('fin');
             $("div#loading-spinner").hide();
         }
     );
 }
 
+function on_clustering_table_file() {
+    var file = $('input#table-file')[0].files[0];
+    if (!file) return;
+    var reader = new FileReader();
+    reader.onload = function() {
+        $('textarea#table-input').val(reader.result);
+        clustering_table_preview();
+    };
+    reader.readAsText(file);
+}
+
+function clustering_table_preview() {
+    $.ajax({
+        type: "post",
+        url: '/preview/1',
+        dataType : "html",
+        contentType : "application/json",
+        data: JSON.stringify({
+            text: $('textarea#table-input').val(),
+            layout: $('select#table-layout').val(),
+        }),
+        success: function(d) {
+            $('div#table-preview').html(d);
+            var count = parseInt($('p#table-preview-summary').attr('data-count')) || 0;
+            $('button#table-apply').prop('disabled', count == 0);
+        },
+    });
+}
+
+function clustering_table_apply() {
+    use_yorn_modal('导入表格', '用预览内容<span class="text-danger">替换</span>当前的全部聚类？替换后需点保存才会写入。', function() {
+        $('div#data-container').html($('div#table-preview-cards').html());
+        $('div#table-preview').html('');
+        $('button#table-apply').prop('disabled', true);
+    });
+}
+
+function facet_new_facet() {
+    $('table#facet-table thead tr th:last-child').before(
+        '<th><input type="text" class="form-control facet-name" placeholder="面"></th>');
+    $('table#facet-table tbody tr td:last-child').before(
+        '<td><input type="text" class="form-control"></td>');
+}
+
+function facet_new_row() {
+    var n = $('table#facet-table thead input.facet-name').length;
+    var row = '<tr>';
+    for (i = 0; i < n; ++i) {
+        row += '<td><input type="text" class="form-control"></td>';
+    }
+    row += '<td><button type="button" class="btn-close" aria-label="Delete" onclick="facet_remove_row(this)"></button></td></tr>';
+    $('table#facet-table tbody').append(row);
+}
+
+function facet_remove_row(btn) {
+    $(btn).closest('tr').remove();
+}
+
+function facet_current_data() {
+    var facets = $('table#facet-table thead input.facet-name').map(function() {
+        return $(this).val().trim();
+    }).get();
+    var items = $('table#facet-table tbody tr').map(function() {
+        return {
+            values: $(this).find('input').map(function() {
+                return $(this).val().trim();
+            }).get(),
+        };
+    }).get();
+    return {
+        facets: facets,
+        items: items,
+        policy: clustering_current_policy(),
+    };
+}
+
+function sequence_new() {
+    $('div#sequence-list').append(
+        '<div class="card m-3 sequence-card"><div class="card-body">' +
+        '<div class="input-group mb-2">' +
+        '<input type="text" class="form-control sequence-name" placeholder="序列名">' +
+        '<button type="button" class="btn btn-outline-danger" onclick="sequence_remove(this)">删除</button>' +
+        '</div>' +
+        '<textarea class="form-control sequence-items" rows="10" placeholder="每行一项，按先后排列"></textarea>' +
+        '</div></div>');
+}
+
+function sequence_remove(btn) {
+    $(btn).closest('div.sequence-card').remove();
+}
+
+function sequence_current_data() {
+    var sequences = $('div#sequence-list div.sequence-card').map(function() {
+        return {
+            name: $(this).find('input.sequence-name').val().trim(),
+            items: $(this).find('textarea.sequence-items').val().split('\n').map(function(s) {
+                return s.trim();
+            }).filter(function(s) {
+                return s != '';
+            }),
+        };
+    }).get();
+    return {
+        sequences: sequences,
+        policy: clustering_current_policy(),
+    };
+}
+
+function hexagram_new_row() {
+    $('table#hexagram-table tbody').append('<tr><td></td>' +
+        '<td><input type="text" class="form-control hexagram-lines" maxlength="6"></td>' +
+        '<td><input type="text" class="form-control hexagram-name"></td>' +
+        '<td><input type="text" class="form-control hexagram-judgement"></td>' +
+        '<td><button type="button" class="btn-close" aria-label="Delete" onclick="facet_remove_row(this)"></button></td></tr>');
+}
+
+function hexagram_current_data() {
+    var hexagrams = $('table#hexagram-table tbody tr').map(function() {
+        return {
+            lines: $(this).find('input.hexagram-lines').val().trim(),
+            name: $(this).find('input.hexagram-name').val().trim(),
+            judgement: $(this).find('input.hexagram-judgement').val().trim(),
+        };
+    }).get();
+    return {
+        hexagrams: hexagrams,
+        policy: clustering_current_policy(),
+    };
+}
 
 function onResize() {
     $("body").css("padding-top", $("nav.fixed-top").height());
@@ -73,10 +73,86 @@ function use_yorn_modal(title, desc, cb) {
 
 var cur_prob_n = null;
 
+var prob_started_at = null;
+
+var countdown_timer = null;
+
+var paper_deadline = null;
+
+var question_deadline = null;
+
+// 欢迎页所选的题干与选项标签，作为出题参数
+function clustering_kinds_query() {
+    var q = '';
+    var heads = $('select#kinds-heads').val();
+    var kinds = $('select#kinds-options').val();
+    if (heads && heads.length > 0) {
+        q += '&heads=' + encodeURIComponent(heads.join(','));
+    }
+    if (kinds && kinds.length > 0) {
+        q += '&kinds=' + encodeURIComponent(kinds.join(','));
+    }
+    return q;
+}
+
 function on_begin_test(type, name) {
+    use_test_page('/exam/' + type + '/' + name + '?' + clustering_kinds_query().substring(1));
+}
+
+function on_begin_weak_test(type, name) {
+    use_test_page('/exam/' + type + '/' + name + '?mode=Weak' + clustering_kinds_query());
+}
+
+function on_begin_sequence_test(type, name, mode) {
+    var seq = $('select#sequence-name').val();
+    use_test_page('/exam/' + type + '/' + name + '?mode=' + mode + '&seq=' + encodeURIComponent(seq));
+}
+
+function on_begin_facet_test(type, name) {
+    var from = $('select#facet-from').val();
+    var to = $('select#facet-to').val();
+    if (from == to) {
+        show_toast('无法出卷', '源面与目标面不能相同', true);
+        return;
+    }
+    use_test_page('/exam/' + type + '/' + name + '?from=' + encodeURIComponent(from) +
+        '&to=' + encodeURIComponent(to));
+}
+
+function on_begin_typed_test(type, name) {
+    use_test_page('/exam/' + type + '/' + name + '?mode=Typed' + clustering_kinds_query());
+}
+
+function on_begin_matching_test(type, name) {
+    use_test_page('/exam/' + type + '/' + name + '?mode=Matching' + clustering_kinds_query());
+}
+
+function on_begin_multi_test(type, name) {
+    use_test_page('/exam/' + type + '/' + name + '?mode=Multi' + clustering_kinds_query());
+}
+
+function on_begin_review(type, name) {
+    use_test_page('/review/' + type + '/' + name + '/20?' + clustering_kinds_query().substring(1));
+}
+
+function on_retake_test(seed, url) {
+    use_yorn_modal('确认', '按种子' + seed + '重新生成同一份试卷？', function() {
+        use_test_page(url);
+    });
+}
+
+function on_resume_test(paper_id) {
+    use_test_page('/resume/' + paper_id);
+}
+
+function use_test_page(url) {
     use_central_loading_mark();
-    $('div#mainpage').load('/exam/' + type + '/' + name + '/100', function() {
-        clustering_pick_prob(0);
+    $('div#mainpage').load(url, function() {
+        var c = $('div#test-container');
+        $('div#test-container div.card[aria-label!="DA"]').removeAttr('hidden');
+        paper_deadline = clustering_deadline(c.attr('data-remaining-ms'));
+        clustering_pick_prob(parseInt(c.attr('data-start')),
+            clustering_deadline(c.attr('data-question-remaining-ms')));
     });
     $(document).keyup(clustering_keyup);
 }
@@ -92,22 +168,79 @@ function on_begin_test_sel() {
     }
 }
 
-function clustering_pick_prob(n) {
+function clustering_pick_prob(n, deadline) {
     cur_prob_n = n;
     $('div#mainpage div#test-container div.card').hide();
     var q = $('div#mainpage div#test-container div.card#test-card-' + n);
-    if (q.length == 0 || clustering_probtype_count("WA") >= 10) {
+    if (q.length == 0) {
         clustering_summary();
         return;
     }
     enforce_show(q);
+    q.find('input[type="text"]').focus();
     clustering_set_progress(n);
+    prob_started_at = Date.now();
+    if (deadline == null) {
+        var limit = parseInt($('div#test-container').attr('data-question-limit-ms'));
+        deadline = limit > 0 ? Date.now() + limit : null;
+    }
+    clustering_start_countdown(deadline);
+}
+
+// 剩余毫秒数转为本地截止时间，空表示不限时
+function clustering_deadline(ms) {
+    var n = parseInt(ms);
+    return isNaN(n) ? null : Date.now() + n;
+}
+
+function clustering_start_countdown(deadline) {
+    clustering_stop_countdown();
+    question_deadline = deadline;
+    if (paper_deadline == null && question_deadline == null) {
+        $('span#test-countdown').text('');
+        return;
+    }
+    countdown_timer = setInterval(clustering_countdown_tick, 200);
+    clustering_countdown_tick();
+}
+
+function clustering_stop_countdown() {
+    if (countdown_timer != null) {
+        clearInterval(countdown_timer);
+        countdown_timer = null;
+    }
+}
+
+function clustering_countdown_tick() {
+    if ($('div#test-container').length == 0) {
+        // 已离开作答页
+        clustering_stop_countdown();
+        return;
+    }
+    var left = Infinity;
+    if (paper_deadline != null) {
+        left = Math.min(left, paper_deadline - Date.now());
+    }
+    if (question_deadline != null) {
+        left = Math.min(left, question_deadline - Date.now());
+    }
+    $('span#test-countdown').text('剩余' + Math.max(0, Math.ceil(left / 1000)) + '秒');
+    if (left <= 0) {
+        clustering_stop_countdown();
+        on_clustering_choose(cur_prob_n, -1);
+    }
 }
 
 function clustering_keyup(event) {
+    if ($(event.target).is('input[type="text"]')) {
+        // 默写题的输入
+        return;
+    }
     var n = cur_prob_n;
     if (event.keyCode >= '1'.charCodeAt() && event.keyCode <= '4'.charCodeAt()) {
         $('input#option-radio-' + n + '-' + (event.keyCode - 49)).click();
+    } else if (event.keyCode == 13) {
+        $('div#mainpage div#test-container div.card#test-card-' + n + ' button[aria-label="opt-submit"]').click();
     } else if (event.keyCode == '0'.charCodeAt()) {
         $('div#mainpage div#test-container div.card#test-card-' + n + ' button[aria-label="opt-surrender"]').click();
     }
@@ -129,31 +262,26 @@ function clustering_probtype_count(typ) {
     return $('div#test-container div.card[aria-label="' + typ + '"]').length;
 }
 
-function clustering_summary() {
-    $('div#test-container button[aria-label="opt-surrender"]').remove();
+// score 缺省时按答对的题卡计分
+function clustering_summary(saved, score) {
+    clustering_stop_countdown();
+    $('span#test-countdown').text('');
+    $('div#test-container button[aria-label="opt-surrender"], div#test-container button[aria-label="opt-submit"]').remove();
     $('div#test-container div.card[aria-label="DA"]').remove();
     $('div#mainpage div#test-container div.card').show();
     enforce_show($('button#wa-only-toggle'));
-    var n = clustering_probtype_count("AC");
+    var n = score == undefined ? clustering_probtype_count("AC") : score;
     clustering_set_progress(n, 'bg-success').text(n + '/' + cur_prob_n + '分');
     $(document).unbind('keyup', clustering_keyup);
-    save_history();
+    if (saved) {
+        show_toast('保存测试结果', saved, saved == '保存成功');
+    }
 }
 
 function clustering_toggle_waonly() {
     $('div#test-container div.card[aria-label="AC"]').toggle();
 }
 
-function save_history() {
-    do_post_json('/save_history', {
-        typ: cur_test_typeid(),
-        name: cur_test_name(),
-        data: $('div#mainpage').html(),
-    }, function(d) {
-        show_toast('保存测试结果', String(d), d=='保存成功');
-    })
-}
-
 function show_toast(title, body, short) {
     $('div#liveToast strong').text(title);
     var ts = new Date().toLocaleString();
@@ -174,27 +302,120 @@ function enforce_show(q) {
     q.show();
 }
 
-function on_clustering_choose(itemid, optid, answer) {
-    enforce_show($('div#test-container div#option-' + itemid + '-' + optid + ' span.badge'));
-    enforce_show($('div#test-container div#option-' + itemid + '-' + answer + ' span.badge'));
-    var q = $('div#test-container div.card#test-card-' + itemid);
-    if (optid != answer) {
-        enforce_show($('div#test-container p#explain-' + itemid));
-        q.attr('aria-label', 'WA');
-    } else {
-        q.attr('aria-label', 'AC');
+function on_clustering_choose(itemid, optid) {
+    if ($('div#test-container div.card#test-card-' + itemid).attr('data-grid-size')) {
+        // 连线题：倒计时结束或按0放弃整组
+        on_matching_submit(itemid, true);
+        return;
+    }
+    clustering_post_answer(itemid, {chosen: optid < 0 ? null : optid}, function(g) {
+        enforce_show($('div#test-container div#option-' + itemid + '-' + g.answer + ' span.badge.bg-success'));
+        if (!g.correct) {
+            enforce_show($('div#test-container div#option-' + itemid + '-' + optid + ' span.badge.bg-danger'));
+        }
+    });
+}
+
+function on_typed_keydown(event, itemid) {
+    if (event.keyCode == 13) {
+        on_typed_submit(itemid);
     }
-    $('div#test-container div.card#test-card-' + itemid + ' input').attr('disabled', 'true');
-    function f1() {
-        clustering_pick_prob(itemid + 1);
+}
+
+function on_typed_submit(itemid) {
+    var typed = $('input#typed-input-' + itemid).val();
+    if (typed.trim() == '') {
+        return;
     }
-    setTimeout(f1, 1000);
+    clustering_post_answer(itemid, {typed: typed});
+}
+
+function on_multi_submit(itemid) {
+    var card = 'div#test-container div.card#test-card-' + itemid;
+    var picks = $(card + ' input[type="checkbox"]:checked').map(function() {
+        return parseInt($(this).val());
+    }).get();
+    clustering_post_answer(itemid, {picks: picks}, function(g) {
+        for (var i = 0; i < g.answers.length; ++i) {
+            enforce_show($(card + ' div#option-' + itemid + '-' + g.answers[i] + ' span.badge.bg-success'));
+        }
+        for (var i = 0; i < picks.length; ++i) {
+            if (g.answers.indexOf(picks[i]) < 0) {
+                enforce_show($(card + ' div#option-' + itemid + '-' + picks[i] + ' span.badge.bg-danger'));
+            }
+        }
+        if (!g.correct) {
+            g.explain = '得' + Math.round(g.credit * 100) + '%；' + g.explain;
+        }
+    });
+}
+
+function on_matching_submit(itemid, surrender) {
+    var card = 'div#test-container div.card#test-card-' + itemid;
+    var size = parseInt($(card).attr('data-grid-size'));
+    var pairs = [];
+    for (var i = 0; i < size; ++i) {
+        var v = parseInt($(card + ' select#grid-select-' + itemid + '-' + i).val());
+        pairs.push(surrender || v < 0 ? null : v);
+    }
+    clustering_post_answer(itemid, {chosen: null, pairs: pairs}, function(g) {
+        for (var i = 0; i < g.pairs.length; ++i) {
+            var row = card + ' tr#grid-row-' + itemid + '-' + i;
+            enforce_show($(row + ' span.badge.' + (g.pairs[i].correct ? 'bg-success' : 'bg-danger')));
+        }
+    });
+}
+
+// 提交第 itemid 题的作答（payload 为所选、默写输入或连线），判分后由 reveal 标出选项，再进入下一题
+function clustering_post_answer(itemid, payload, reveal) {
+    clustering_stop_countdown();
+    var card = 'div#test-container div.card#test-card-' + itemid;
+    $(card + ' input, ' + card + ' select, ' + card + ' button').attr('disabled', 'true');
+    do_post_json('/answer', $.extend({
+        paper_id: $('div#test-container').attr('data-paper-id'),
+        prob: itemid,
+        elapsed_ms: Date.now() - prob_started_at,
+    }, payload), function(d) {
+        if (d.Err) {
+            show_toast('作答错误', d.Err);
+            return;
+        }
+        var g = d.Ok;
+        paper_deadline = g.remaining_ms == null ? null : Date.now() + g.remaining_ms;
+        if (g.timed_out) {
+            show_toast('超时', '第' + (itemid + 1) + '题超过限时，按错判', true);
+        }
+        if (reveal) {
+            reveal(g);
+        }
+        if (g.correct) {
+            enforce_show($(card + ' span.badge.bg-success.verdict'));
+            $(card).attr('aria-label', 'AC');
+        } else {
+            enforce_show($(card + ' span.badge.bg-danger.verdict'));
+            enforce_show($('div#test-container p#explain-' + itemid).text(g.explain));
+            $(card).attr('aria-label', 'WA');
+        }
+        var next = itemid + Math.max(1, g.pairs.length);
+        setTimeout(function() {
+            if (g.finished) {
+                cur_prob_n = next;
+                clustering_summary(g.saved, g.score);
+            } else {
+                clustering_pick_prob(next);
+            }
+        }, 1000);
+    });
 }
 
 function on_show_history_list(type, name) {
     $('div#mainpage').load('/list_history/' + type + '/' + name);
 }
 
+function on_show_stats(type, name) {
+    $('div#mainpage').load('/stats/' + type + '/' + name);
+}
+
 function on_show_history_list_sel() {
     var id = cur_test_typeid();
     var name = cur_test_name();